@@ -4,14 +4,22 @@
 //! - Interaktive Eingabe von Optionen und Speichern in der Konfigurationsdatei
 //! - Ausführen des Programms unter Berücksichtigung der eingegebenen Optionen
 
-use std::{fs, io::ErrorKind, path::{Path, PathBuf}, process, sync::Mutex, thread};
+use std::{collections::BTreeMap, fs, io::ErrorKind, path::{Path, PathBuf}, process::ExitCode, sync::mpsc::{self, Receiver}, thread, time::Duration};
 
 use anyhow::{anyhow, bail, Result};
 use glam::Vec3;
 use macroquad::color::Color;
 use serde::{de::{Unexpected, Visitor}, Deserialize, Deserializer, Serialize, Serializer};
 
-use crate::{monitor::{self, PlasmaMonitor, OrientationVectors, Rotation}, rotate_image, serial::{SerialPortName, SerialReader}};
+use crate::{backend::{BackendKind, CommandTemplates, DisplayBackend}, calibration::{self, CheckStatus, QualityReport, SensorCalibration}, command::CommandSettings, filter::{self, MotionSettings}, hooks::HookSettings, inhibit::InhibitSettings, input::InputMapping, lock::RotationLock, notify::{Category, NotificationSettings}, monitor::{self, MonitorGroup, PlasmaMonitor, OrientationVectors, Rotation, RotationSettings}, rotate_image::{self, ImageSettings}, serial::{SensorDisconnected, SerialPortName, SerialReader}, signals::SignalFlags};
+
+
+/// Exit-Status von `rotate-monitor --once`, wenn die Rotation nicht geändert wurde.
+/// 2 ist bereits für fehlerhafte Eingabeargumente (clap) vergeben.
+const UNCHANGED_EXIT_CODE: u8 = 3;
+
+/// Abstand, in dem nach einer Trennung versucht wird, den Sensor erneut zu öffnen.
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(2);
 
 
 /// Eine Konvertierung zum/vom JSON-Format ist nur möglich, wenn ein Objekt [`Serialize`]
@@ -106,6 +114,27 @@ struct ConfigSettings {
     #[serde(skip_serializing_if = "Option::is_none")]
     monitor: Option<PlasmaMonitor>,
 
+    /// Mehrere gemeinsam rotierte Bildschirme, jeweils optional mit Versatz.
+    /// Wird anstelle von `monitor` gespeichert, sobald mehr als ein Bildschirm oder ein Versatz angegeben ist.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    monitors: Option<Vec<PlasmaMonitor>>,
+
+    /// Zeit in Sekunden, die beim Start auf einen fehlenden oder getrennten Bildschirm gewartet wird.
+    /// Kann nur in der Konfigurationsdatei angegeben werden.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    monitor_wait_seconds: Option<u64>,
+
+    /// Befehlsvorlagen für nicht unterstützte Desktop-Umgebungen (siehe [`CommandTemplates`]).
+    /// Kann nur in der Konfigurationsdatei angegeben werden.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    command_templates: Option<CommandTemplates>,
+
+    /// Zeitlimit, Wiederholungen und Fehlerbehandlung für `kscreen-doctor` bzw. die Befehlsvorlagen
+    /// (siehe [`CommandSettings`]).
+    /// Kann nur in der Konfigurationsdatei angegeben werden.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    display_commands: Option<CommandSettings>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     image_path: Option<PathBuf>,
 
@@ -115,8 +144,98 @@ struct ConfigSettings {
     #[serde(skip_serializing_if = "Option::is_none")]
     orientations: Option<OrientationVectors>,
 
+    /// Korrektur der Rohwerte des Sensors (siehe [`SensorCalibration`])
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sensor_calibration: Option<SensorCalibration>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    background_color: Option<HexColorSerde>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    restore_rotation: Option<bool>,
+
+    /// Mindestwinkel in Grad, um den eine neue Ausrichtung besser passen muss als die aktuelle.
+    /// Kann nur in der Konfigurationsdatei angegeben werden.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hysteresis_degrees: Option<f32>,
+
+    /// Zeit in Millisekunden, die eine neue Ausrichtung ununterbrochen gewinnen muss, bevor rotiert wird.
+    /// Kann nur in der Konfigurationsdatei angegeben werden.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dwell_time_ms: Option<u64>,
+
+    /// Neigung aus der Rotationsebene in Grad, ab der der Bildschirm als flach liegend gilt
+    /// und die aktuelle Ausrichtung bzw. der aktuelle Bildwinkel beibehalten wird.
+    /// Kann nur in der Konfigurationsdatei angegeben werden.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    flat_threshold_degrees: Option<f32>,
+
+    /// Schwellwerte, ab denen das Gerät als bewegt gilt (siehe [`MotionSettings`]).
+    /// Kann nur in der Konfigurationsdatei angegeben werden.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    motion: Option<MotionSettings>,
+
+    /// Friert den Bildwinkel in `rotate-image` ein, solange das Gerät bewegt wird.
+    /// Kann nur in der Konfigurationsdatei angegeben werden.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    freeze_image_in_motion: Option<bool>,
+
+    /// Pfad der Sperrdatei (siehe [`RotationLock`]).
+    /// Kann nur in der Konfigurationsdatei angegeben werden.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lock_file: Option<PathBuf>,
+
+    /// Behält die Rotationssperre über einen Neustart hinweg bei.
+    /// Andernfalls wird sie beim Start von `rotate-monitor` aufgehoben.
+    /// Kann nur in der Konfigurationsdatei angegeben werden.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    persist_lock: Option<bool>,
+
+    /// Befehle, die nach jeder Rotation ausgeführt werden (siehe [`HookSettings`]).
+    /// Kann nur in der Konfigurationsdatei angegeben werden.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hooks: Option<HookSettings>,
+
+    /// Touchscreens und Stifte, die zusammen mit dem Bildschirm rotiert werden (siehe [`InputMapping`]).
+    /// Kann nur in der Konfigurationsdatei angegeben werden.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    input_mapping: Option<InputMapping>,
+
+    /// Bedingungen, unter denen die automatische Rotation angehalten wird (siehe [`InhibitSettings`]).
+    /// Kann nur in der Konfigurationsdatei angegeben werden.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    inhibitors: Option<InhibitSettings>,
+
+    /// Desktop-Benachrichtigungen bei Rotation, Sperre, getrenntem Sensor und Fehlern (siehe [`NotificationSettings`]).
+    /// Kann nur in der Konfigurationsdatei angegeben werden.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    notifications: Option<NotificationSettings>,
+
+    /// Weitere Sensoren mit eigenen Bildschirmen und Richtungsvektoren (siehe [`SensorConfig`]).
+    /// Sie werden in `rotate-monitor` gleichzeitig mit dem Hauptsensor ausgewertet.
+    /// Kann nur in der Konfigurationsdatei angegeben werden.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sensors: Option<Vec<SensorConfig>>
+}
+
+/// Ein weiterer Sensor, der unabhängig vom Hauptsensor eigene Bildschirme rotiert,
+/// z.B. bei zwei einzeln drehbaren Bildschirmen an einem Arbeitsplatz.
+/// Alle übrigen Einstellungen (Hysterese, Hooks, Sperre, ...) gelten für alle Sensoren gemeinsam.
+#[derive(Serialize, Deserialize, Clone)]
+struct SensorConfig {
+    serial_port: SerialPortName,
+
+    /// Bildschirme dieses Sensors, jeweils optional mit Versatz.
+    monitors: Vec<PlasmaMonitor>,
+
+    /// Richtungsvektoren dieses Sensors.
+    /// Fehlen sie, wird der Sensor beim nächsten interaktiven Start kalibriert.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    orientations: Option<OrientationVectors>,
+
+    /// Korrektur der Rohwerte dieses Sensors (siehe [`SensorCalibration`]).
     #[serde(skip_serializing_if = "Option::is_none")]
-    background_color: Option<HexColorSerde>
+    calibration: Option<SensorCalibration>,
 }
 
 impl ConfigSettings {
@@ -140,6 +259,83 @@ impl ConfigSettings {
         }
     }
 
+    /// Erzeugt die Einstellungen für [`monitor::run_automatic_rotation`] aus dieser Konfiguration.
+    /// Die Richtungsvektoren werden separat übergeben, da sie auch interaktiv berechnet worden sein können
+    /// (siehe [`reloaded_orientations`](Self::reloaded_orientations)).
+    fn rotation_settings(&self, orientations: OrientationVectors) -> RotationSettings {
+        RotationSettings {
+            orientations,
+            hysteresis_degrees: self.hysteresis_degrees.unwrap_or(filter::DEFAULT_HYSTERESIS_DEGREES),
+            dwell_time: Duration::from_millis(self.dwell_time_ms.unwrap_or(filter::DEFAULT_DWELL_TIME_MS)),
+            flat_threshold_degrees: self.flat_threshold_degrees(),
+            motion: self.motion.clone().unwrap_or_default(),
+            lock: self.rotation_lock(),
+            hooks: self.hooks.clone().unwrap_or_default(),
+            input_mapping: self.input_mapping.clone(),
+            notifications: self.notifications.clone(),
+            inhibitors: self.inhibitors.clone().unwrap_or_default(),
+        }
+    }
+
+    /// Erzeugt die Einstellungen für einen weiteren Sensor.
+    /// Eingabegeräte ohne explizit zugeordneten Bildschirm gehören nur zum Hauptsensor.
+    /// Die Sperre ist allen Sensoren gemeinsam und wird daher nur vom Hauptsensor gemeldet.
+    fn sensor_rotation_settings(&self, orientations: OrientationVectors) -> RotationSettings {
+        RotationSettings {
+            input_mapping: self.input_mapping.clone().filter(|m| m.monitor.is_some()),
+            notifications: self.notifications.clone().map(|n| NotificationSettings { lock: false, ..n }),
+            ..self.rotation_settings(orientations)
+        }
+    }
+
+    /// Gibt die gespeicherten Richtungsvektoren des weiteren Sensors am angegebenen Anschluss zurück.
+    fn sensor_orientations(&self, port_name: &str) -> Option<OrientationVectors> {
+        self.sensors
+            .iter()
+            .flatten()
+            .find(|sensor| sensor.serial_port.to_string() == port_name)
+            .and_then(|sensor| sensor.orientations.clone())
+    }
+
+    /// Wählt beim Neuladen der Konfiguration die Richtungsvektoren aus.
+    ///
+    /// Die Vektoren im Speicher haben Vorrang, da sie beim Start neu berechnet, aber nicht gespeichert worden sein können.
+    /// Die Vektoren aus der Datei werden nur übernommen, wenn sie sich dort seit dem Start geändert haben.
+    fn reloaded_orientations(on_disk: Option<OrientationVectors>, at_start: &Option<OrientationVectors>, in_memory: &OrientationVectors) -> OrientationVectors {
+        on_disk
+            .filter(|orientations| Some(orientations) != at_start.as_ref())
+            .unwrap_or_else(|| in_memory.clone())
+    }
+
+    /// Überträgt beim Start erkannte Umbenennungen von Anschlüssen auf den Bildschirm der Eingabegeräte.
+    fn rename_input_monitor(&mut self, renames: &[(String, String)]) {
+        let Some(monitor) = self.input_mapping.as_mut().and_then(|m| m.monitor.as_mut()) else { return };
+
+        if let Some((_, new)) = renames.iter().find(|(old, _)| old == monitor) {
+            *monitor = new.clone();
+        }
+    }
+
+    /// Speichert die Bildschirme in der Konfiguration.
+    /// Ein einzelner Bildschirm ohne Versatz wird weiterhin im bisherigen Format als `monitor` gespeichert.
+    fn set_monitors(&mut self, mut monitors: Vec<PlasmaMonitor>) {
+        if let [monitor] = monitors.as_slice() && monitor.offset.is_none() {
+            self.monitor = monitors.pop();
+        } else {
+            self.monitors = Some(monitors);
+        }
+    }
+
+    /// Gibt die Rotationssperre mit der konfigurierten Sperrdatei zurück.
+    fn rotation_lock(&self) -> RotationLock {
+        RotationLock::new(self.lock_file.clone())
+    }
+
+    /// Gibt die Neigung zurück, ab der der Bildschirm als flach liegend gilt.
+    fn flat_threshold_degrees(&self) -> f32 {
+        self.flat_threshold_degrees.unwrap_or(filter::DEFAULT_FLAT_THRESHOLD_DEGREES)
+    }
+
     /// Serialisiert diese [`ConfigSettings`] in die angegebene Datei.
     fn save_to_file(&self, file: &Path) -> Result<()> {
         let json_string = serde_json::to_string_pretty(self)?;
@@ -150,6 +346,103 @@ impl ConfigSettings {
 }
 
 
+/// Alles, was ein Thread benötigt, um die Bildschirme eines Sensors automatisch zu rotieren.
+struct SensorTask<'a> {
+    /// Bezeichnung des Sensors für Fehlermeldungen.
+    label: String,
+    settings: RotationSettings,
+    group: MonitorGroup,
+
+    /// Anschluss des Sensors, um ihn nach einer Trennung erneut zu öffnen.
+    serial_port: SerialPortName,
+    serial_reader: SerialReader,
+    signals: SignalFlags,
+    reload_settings: Box<dyn Fn() -> Result<RotationSettings> + Send + 'a>,
+}
+
+impl SensorTask<'_> {
+    /// Führt die automatische Rotation aus.
+    /// Auf Wunsch werden beim Beenden die Rotationen wiederhergestellt, die die Bildschirme beim Start hatten.
+    fn run(mut self, backend: &DisplayBackend, restore_rotation: bool) -> Result<()> {
+        // Frage die aktuellen Rotationen ab, damit sie beim Beenden wiederhergestellt werden können.
+        self.group.refresh_state(backend)?;
+        let original_rotations = self.group.current_rotations();
+
+        // Nach einer Trennung wird auf den Sensor gewartet und die Rotation anschließend fortgesetzt.
+        let result = loop {
+            // Neu geladene Einstellungen bleiben in `self.settings` auch über eine Trennung hinweg erhalten.
+            let result = monitor::run_automatic_rotation(
+                &mut self.settings,
+                backend,
+                &mut self.group,
+                &mut self.serial_reader,
+                &self.signals,
+                // Bei SIGHUP wird die Konfigurationsdatei neu eingelesen.
+                &self.reload_settings,
+            );
+
+            match &result {
+                Err(e) if let Some(disconnected) = e.downcast_ref::<SensorDisconnected>() => {
+                    eprintln!("{}: {disconnected}; warte auf erneute Verbindung", self.label);
+                    self.settings.notify(Category::Sensor, "Sensor getrennt", &format!("{}: {}", self.label, disconnected.0));
+                }
+                _ => break result,
+            }
+
+            if !self.reconnect() {
+                break Ok(());
+            }
+
+            eprintln!("{}: Sensor wieder verbunden", self.label);
+            self.settings.notify(Category::Sensor, "Sensor wieder verbunden", &self.label);
+        };
+
+        if let Err(e) = &result {
+            self.settings.notify(Category::Failure, "Automatische Rotation beendet", &format!("{}: {e}", self.label));
+        }
+
+        // Stelle die ursprünglichen Rotationen wieder her, auch wenn ein Fehler aufgetreten ist.
+        // Ein Fehler der Schleife hat dabei Vorrang vor einem Fehler bei der Wiederherstellung.
+        let restored = match restore_rotation {
+            true => self.group.restore(backend, &original_rotations),
+            false => Ok(())
+        };
+
+        let result = result.and(restored);
+        if let Err(e) = &result {
+            eprintln!("{}: {e}", self.label);
+        }
+        result
+    }
+
+    /// Rotiert die Bildschirme einmalig anhand gemittelter Messwerte (siehe [`monitor::rotate_once`]).
+    /// Gibt zurück, ob sich die Rotation geändert hat.
+    fn run_once(mut self, backend: &DisplayBackend) -> Result<bool> {
+        let result = monitor::rotate_once(&self.settings, backend, &mut self.group, &mut self.serial_reader);
+        if let Err(e) = &result {
+            eprintln!("{}: {e}", self.label);
+        }
+        result
+    }
+
+    /// Versucht in regelmäßigen Abständen, den Anschluss des Sensors erneut zu öffnen.
+    /// Gibt `false` zurück, wenn das Programm vorher beendet werden soll.
+    fn reconnect(&mut self) -> bool {
+        while !self.signals.should_terminate() {
+            if let Ok(mut reader) = self.serial_port.open() {
+                reader.set_calibration(self.serial_reader.calibration().cloned());
+                self.serial_reader = reader;
+                return true;
+            }
+
+            thread::sleep(RECONNECT_INTERVAL);
+        }
+
+        false
+    }
+}
+
+
 /// Hilfsstruktur, die die automatische Verarbeitung von Eingabeargumenten über
 /// das [`clap`]-Interface ermöglicht.
 #[derive(clap::Parser)]
@@ -171,6 +464,11 @@ pub struct Args {
     #[arg(long)]
     recalculate_vectors: bool,
 
+    /// Kalibriert (interaktiv) Nullpunkt und Skalierung der Achsen des Beschleunigungssensors.
+    /// Die Richtungsvektoren werden anschließend neu berechnet
+    #[arg(long)]
+    calibrate_sensor: bool,
+
     /// Der auszuführende Befehl
     #[command(subcommand)]
     mode: Commands
@@ -181,9 +479,25 @@ pub struct Args {
 enum Commands {
     /// Liest die Rotationsdaten und rotiert den Bildschirm automatisch um Vielfache von 90°
     RotateMonitor {
-        /// Name des Bildschirms
+        /// Name des Bildschirms, optional mit Versatz zum Sensor (`NAME:right`).
+        /// Kann mehrfach angegeben werden, um mehrere Bildschirme gemeinsam zu rotieren
+        #[arg(long)]
+        monitor: Vec<String>,
+
+        /// Stellt beim Beenden (Strg+C, SIGTERM) die Rotation wieder her, die der Bildschirm beim Start hatte
         #[arg(long)]
-        monitor: Option<String>,
+        restore_rotation: bool,
+
+        /// Rotiert nicht, sondern gibt jede Entscheidung mit Zeitpunkt, Messwert und Winkeln zu allen Ausrichtungen aus.
+        /// Rotiert nie über `kscreen-doctor` und funktioniert daher auch ohne Plasma;
+        /// mit `--once` wird die aktuelle Rotation darüber nur gelesen, sofern möglich
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Mittelt einige Messwerte, rotiert einmalig und beendet sich, z.B. für Tastenkürzel oder Anmeldeskripte.
+        /// Exit-Status 0: Rotation geändert, 3: Rotation unverändert, 1: Fehler, 2: ungültige Argumente
+        #[arg(long, conflicts_with = "restore_rotation")]
+        once: bool,
     },
 
     /// Liest die Rotationsdaten und stabilisiert ein Bild, sodass es immer parallel zum Erdboden ausgerichtet bleibt
@@ -199,17 +513,54 @@ enum Commands {
         /// Hexcode für die Hintergrundfarbe des Fensters
         #[arg(long, value_parser = HexColorSerde::try_parse_hex_str, default_value = "#000000")]
         background_color: Option<Color>
+    },
+
+    /// Sperrt oder entsperrt die Rotation eines laufenden `rotate-monitor`-Prozesses
+    Lock {
+        /// Auszuführende Aktion
+        #[arg(value_enum, default_value_t = LockAction::Toggle)]
+        action: LockAction
     }
 }
 
+/// Aktionen des Befehls [`Commands::Lock`].
+#[derive(clap::ValueEnum, Clone, Copy)]
+enum LockAction {
+    /// Schaltet die Sperre um
+    Toggle,
+
+    /// Sperrt die Rotation
+    On,
+
+    /// Entsperrt die Rotation
+    Off,
+
+    /// Gibt aus, ob die Rotation gesperrt ist
+    Status,
+}
+
 impl Args {
     /// Haupteintrittspunkt des Programms, nachdem alle Eingabeargumente verarbeitet wurden.
     /// Liest eine Konfigurationsdatei ein, wenn diese angegeben wurde, und führt den als Eingabeargument übergebenen Befehl aus.
-    pub fn run_selected_mode(self) -> Result<()> {
+    /// Gibt den Exit-Status zurück, mit dem sich das Programm beenden soll.
+    pub fn run_selected_mode(self) -> Result<ExitCode> {
         // Lese die Konfigurationsdatei ein.
         // Wenn keine angegeben wurde, sind alle Felder [`None`].
         let mut config = ConfigSettings::from_file_or_default(&self.config)?;
 
+        // Die Richtungsvektoren, wie sie beim Start in der Datei stehen, um Änderungen beim Neuladen zu erkennen.
+        let file_orientations = config.orientations.clone();
+
+        // Das Sperren benötigt weder Sensor noch Monitor und wird daher direkt ausgeführt.
+        if let Commands::Lock { action } = self.mode {
+            Self::run_lock_action(action, &config.rotation_lock())?;
+            return Ok(ExitCode::SUCCESS);
+        }
+
+        // Registriere die Signal-Handler so früh wie möglich,
+        // damit auch eine abgebrochene Kalibrierung den Bildschirm wiederherstellen kann.
+        let signals = SignalFlags::register()?;
+
         // Behalte im Überblick, ob interaktive Änderungen an der Konfiguration durchgeführt worden sind.
         // Wenn das der Fall ist, wird am Ende angeboten, die aktuelle Konfiguration in einer Datei zu speichern.
         let mut user_input_made = false;
@@ -217,8 +568,10 @@ impl Args {
         // Überprüft, ob ein Monitorname oder Bilddateipfad in den Eingabeargumenten enthalten ist
         // und ob dieser für den jeweiligen Modus benötigt wird.
         let (args_monitor, args_image_path, monitor_required, image_path_required) = match &self.mode {
-            Commands::RotateMonitor { monitor } => (monitor.as_deref(), None, true, false),
-            Commands::RotateImage { image_path, .. } => (None, image_path.as_deref(), false, true),
+            // Im Probelauf wird der Bildschirm nur für die Protokollierung benötigt.
+            Commands::RotateMonitor { monitor, dry_run, .. } => (monitor.as_slice(), None, !dry_run, false),
+            Commands::RotateImage { image_path, .. } => (&[][..], image_path.as_deref(), false, true),
+            Commands::Lock { .. } => (&[][..], None, false, false),
         };
 
         // Die nächsten vier Abschnitte folgen alle demselben Schema:
@@ -228,7 +581,7 @@ impl Args {
         // - Im nicht-interaktiven Modus wird ein Fehler zurückgegeben, sofern der Wert für den ausgewählten Modus benötigt wird.
         // Bei interaktiven Eingaben wird der neue Wert in der Konfiguration zwischengespeichert.
         // Wenn der Benutzer das Speichern der Konfiguration ablehnt, bleibt die Datei unverändert.
-        let mut serial_reader = {
+        let (mut serial_reader, serial_port) = {
             let serial_port = if let Some(name) = self.serial_port {
                 SerialPortName::from_string(name)
             } else if let Some(port) = config.serial_port {
@@ -240,38 +593,85 @@ impl Args {
                 bail!("Serieller Anschluss wurde nicht angegeben")
             };
             let reader = serial_port.open()?;
-            config.serial_port = Some(serial_port);
-            reader
+            config.serial_port = Some(serial_port.clone());
+            (reader, serial_port)
+        };
+
+        // Die Korrektur des Sensors muss vor den Richtungsvektoren feststehen, da diese aus korrigierten Werten berechnet werden.
+        // Nach einer neuen Kalibrierung des Sensors passen die bisherigen Richtungsvektoren daher nicht mehr.
+        if self.calibrate_sensor {
+            user_input_made = true;
+            config.sensor_calibration = Some(Self::calibrate_sensor(&mut serial_reader, &signals)?);
+        }
+        serial_reader.set_calibration(config.sensor_calibration.clone());
+        let recalculate_vectors = self.recalculate_vectors || self.calibrate_sensor;
+
+        // Wenn Befehlsvorlagen konfiguriert sind, werden diese anstelle von `kscreen-doctor` verwendet.
+        // Im Probelauf wird gar kein Befehl ausgeführt.
+        let backend = DisplayBackend {
+            kind: match (&self.mode, &config.command_templates) {
+                (Commands::RotateMonitor { dry_run: true, .. }, _) => BackendKind::DryRun,
+                (_, Some(templates)) => BackendKind::Custom(templates.clone()),
+                (_, None) => BackendKind::KScreenDoctor,
+            },
+            commands: config.display_commands.clone().unwrap_or_default(),
         };
 
-        let monitor = {
-            // Die Rotation des gesamten Monitors ist nur unter KDE Plasma unterstützt.
+        // Umbenennungen von Anschlüssen, die beim Start anhand der EDID erkannt wurden.
+        let mut renames = Vec::new();
+
+        let monitor_wait = Duration::from_secs(config.monitor_wait_seconds.unwrap_or(monitor::DEFAULT_MONITOR_WAIT_SECONDS));
+        let monitors = {
+            // Die Rotation des gesamten Monitors ist ohne Befehlsvorlagen nur unter KDE Plasma unterstützt.
             // Wenn kein Plasma erkannt wurde, wird ein Fehler zurückgegeben.
-            let monitor = match Self::check_rotation_supported() {
-                Ok(()) => if let Some(name) = args_monitor {
-                        Ok(PlasmaMonitor { name: name.to_string() })
-                    } else if let Some(monitor) = config.monitor {
-                        Ok(monitor)
+            let config_monitors = config.monitors.take().or_else(|| config.monitor.take().map(|m| vec![m]));
+            let monitors = match Self::check_rotation_supported(&backend) {
+                Ok(()) => if !args_monitor.is_empty() {
+                        args_monitor.iter().map(|arg| PlasmaMonitor::from_arg(arg)).collect()
+                    } else if let Some(monitors) = config_monitors.clone() && !monitors.is_empty() {
+                        Ok(monitors)
                     } else if monitor_required && !self.non_interactive {
                         user_input_made = true;
-                        Ok(Self::select_monitor()?)
+                        Ok(vec![Self::select_monitor(&backend)?])
                     } else {
                         Err(anyhow!("Monitor wurde nicht angegeben"))
                     },
                 Err(e) => Err(e)
             };
-            config.monitor = monitor.as_ref().ok().cloned();
-            monitor
+
+            let mut monitors = monitors;
+            if let Ok(monitors) = &mut monitors && let Some(saved) = &config_monitors {
+                monitors.iter_mut().for_each(|m| m.keep_saved_settings(saved));
+            }
+
+            // Anschlussnamen können sich seit dem Speichern geändert haben; die EDID bleibt gleich.
+            // Ungültige oder fehlende Bildschirme sollen schon beim Start auffallen, nicht erst bei der ersten Rotation.
+            if let Ok(monitors) = &mut monitors {
+                renames.extend(PlasmaMonitor::resolve_connectors(monitors, &backend)?);
+                monitors.iter().try_for_each(|m| backend.validate_monitor(m))?;
+
+                if monitor_required {
+                    PlasmaMonitor::wait_until_available(monitors, &backend, monitor_wait, &signals)?;
+                }
+            }
+
+            // Gespeicherte Bildschirme bleiben erhalten, auch wenn sie in diesem Modus nicht verwendet werden.
+            match monitors.as_deref() {
+                Ok(monitors) => config.set_monitors(monitors.to_vec()),
+                Err(_) => if let Some(monitors) = config_monitors { config.set_monitors(monitors) },
+            }
+            monitors
         };
 
         let orientations = {
             // Die Vektoren können nicht per Eingabeargument übergeben werden.
             // Daher wird dieser Schritt hier übersprungen.
-            let orientations = if let Some(orientations) = config.orientations && !self.recalculate_vectors {
+            let orientations = if let Some(orientations) = config.orientations && !recalculate_vectors {
                 orientations
-            } else if !self.non_interactive || self.recalculate_vectors {
+            } else if !self.non_interactive || recalculate_vectors {
                 user_input_made = true;
-                Self::calculate_vectors(&mut serial_reader, monitor.as_ref().ok())?
+                let mut group = monitors.as_ref().ok().map(|m| MonitorGroup { monitors: m.clone() });
+                Self::calculate_vectors(&mut serial_reader, &backend, group.as_mut(), &signals)?
             } else {
                 bail!("Richtungsvektoren wurden nicht angegeben")
             };
@@ -279,6 +679,42 @@ impl Args {
             orientations
         };
 
+        // Weitere Sensoren werden nur für `rotate-monitor` geöffnet.
+        // Fehlende Richtungsvektoren werden wie beim Hauptsensor interaktiv berechnet.
+        let mut sensors = Vec::new();
+        if let Commands::RotateMonitor { .. } = self.mode {
+            for sensor in config.sensors.iter_mut().flatten() {
+                let port_name = sensor.serial_port.to_string();
+                let file_orientations = sensor.orientations.clone();
+                if sensor.monitors.is_empty() {
+                    bail!("Für den Sensor an {port_name} wurde kein Monitor angegeben");
+                }
+
+                renames.extend(PlasmaMonitor::resolve_connectors(&mut sensor.monitors, &backend)?);
+                sensor.monitors.iter().try_for_each(|m| backend.validate_monitor(m))?;
+                PlasmaMonitor::wait_until_available(&sensor.monitors, &backend, monitor_wait, &signals)?;
+                let mut reader = sensor.serial_port.open()?;
+                if self.calibrate_sensor {
+                    println!("Kalibrierung des Beschleunigungssensors an {port_name}");
+                    sensor.calibration = Some(Self::calibrate_sensor(&mut reader, &signals)?);
+                }
+                reader.set_calibration(sensor.calibration.clone());
+
+                let orientations = if let Some(orientations) = &sensor.orientations && !recalculate_vectors {
+                    orientations.clone()
+                } else if !self.non_interactive || recalculate_vectors {
+                    user_input_made = true;
+                    println!("Kalibrierung des Sensors an {port_name}");
+                    let mut group = MonitorGroup { monitors: sensor.monitors.clone() };
+                    Self::calculate_vectors(&mut reader, &backend, Some(&mut group), &signals)?
+                } else {
+                    bail!("Richtungsvektoren für den Sensor an {port_name} wurden nicht angegeben")
+                };
+                sensor.orientations = Some(orientations.clone());
+                sensors.push((sensor.serial_port.clone(), reader, MonitorGroup { monitors: sensor.monitors.clone() }, orientations, file_orientations));
+            }
+        }
+
         let image_path = {
             let image_path: Result<PathBuf> = if let Some(image_path) = args_image_path {
                 Ok(image_path.to_path_buf())
@@ -294,6 +730,28 @@ impl Args {
             image_path
         };
 
+        // Die Eingabegeräte gehören weiterhin zum selben Bildschirm, auch wenn dieser umgesteckt wurde.
+        config.rename_input_monitor(&renames);
+
+        // Diese Werte müssen vor dem Speichern ausgelesen werden, da die Konfiguration dabei verbraucht wird.
+        let rotation_settings = config.rotation_settings(orientations.clone());
+        let sensors: Vec<_> = sensors
+            .into_iter()
+            .map(|(port, reader, group, orientations, file_orientations)| {
+                let settings = config.sensor_rotation_settings(orientations.clone());
+                (port, reader, group, orientations, file_orientations, settings)
+            })
+            .collect();
+        let flat_threshold_degrees = config.flat_threshold_degrees();
+        let image_motion = config.freeze_image_in_motion
+            .unwrap_or(false)
+            .then(|| config.motion.clone().unwrap_or_default());
+        let restore_rotation = match &self.mode {
+            Commands::RotateMonitor { restore_rotation, .. } => *restore_rotation || config.restore_rotation.unwrap_or(false),
+            _ => false,
+        };
+        let persist_lock = config.persist_lock.unwrap_or(false);
+
         // Wenn die Konfiguration interaktiv geändert wurde:
         // optionales Speichern anbieten.
         if user_input_made {
@@ -302,31 +760,138 @@ impl Args {
 
         // führe den ausgewählten Modus aus
         match self.mode {
-            Commands::RotateMonitor { .. } => {
-                monitor::run_automatic_rotation(
-                    orientations,
-                    monitor?,
-                    serial_reader
-                )
+            Commands::RotateMonitor { dry_run, once, .. } => {
+                // Im Probelauf darf der Bildschirm fehlen, da er nur protokolliert wird.
+                let monitors = if dry_run { monitors.unwrap_or_default() } else { monitors? };
+                let group = MonitorGroup { monitors };
+
+                // Ein Probelauf darf die Sperre eines parallel laufenden Prozesses nicht verändern.
+                // Ein einmaliger Aufruf soll die Sperre beachten und hebt sie daher ebenfalls nicht auf.
+                if !persist_lock && !dry_run && !once {
+                    rotation_settings.lock.set_locked(false)?;
+                }
+
+                // Im Probelauf teilen sich alle Sensoren eine Sperre im Speicher, auch nach dem Neuladen.
+                let dry_run_lock = dry_run.then(|| rotation_settings.lock.in_memory());
+                let dry_run_lock = &dry_run_lock;
+                let for_mode = move |settings: RotationSettings| match dry_run_lock {
+                    Some(lock) => settings.for_dry_run(lock),
+                    None => settings,
+                };
+
+                // Neuladen und Sperre werden nur von der fortlaufenden Rotation ausgewertet.
+                if !once {
+                    signals.handle_reload_and_lock()?;
+                }
+
+                // Jeder Sensor läuft in einem eigenen Thread.
+                // Jeder Thread benötigt ein eigenes Flag für SIGHUP, damit alle die Konfiguration neu laden.
+                let config_path = &self.config;
+                let renames = &renames;
+                let backend = &backend;
+                let mut tasks = vec![SensorTask {
+                    label: "Hauptsensor".to_string(),
+                    settings: for_mode(rotation_settings),
+                    group,
+                    serial_port,
+                    serial_reader,
+                    signals: signals.clone(),
+                    reload_settings: Box::new(move || {
+                        let mut config = ConfigSettings::from_file_or_default(config_path)?;
+                        config.rename_input_monitor(renames);
+                        let orientations = ConfigSettings::reloaded_orientations(config.orientations.clone(), &file_orientations, &orientations);
+                        Ok(for_mode(config.rotation_settings(orientations)))
+                    }),
+                }];
+                for (serial_port, serial_reader, group, orientations, file_orientations, settings) in sensors {
+                    let reload_port = serial_port.to_string();
+                    tasks.push(SensorTask {
+                        label: format!("Sensor an {reload_port}"),
+                        settings: for_mode(settings),
+                        group,
+                        serial_port,
+                        serial_reader,
+                        signals: if once { signals.clone() } else { signals.with_own_reload()? },
+                        reload_settings: Box::new(move || {
+                            let mut config = ConfigSettings::from_file_or_default(config_path)?;
+                            config.rename_input_monitor(renames);
+                            let on_disk = config.sensor_orientations(&reload_port);
+                            let orientations = ConfigSettings::reloaded_orientations(on_disk, &file_orientations, &orientations);
+                            Ok(for_mode(config.sensor_rotation_settings(orientations)))
+                        }),
+                    });
+                }
+
+                // Ein Fehler bei einem Sensor hält die übrigen nicht auf; der erste Fehler wird zurückgegeben.
+                if once {
+                    let mut changed = false;
+                    let mut first_error = None;
+                    for task in tasks {
+                        match task.run_once(backend) {
+                            Ok(task_changed) => changed |= task_changed,
+                            Err(e) => { first_error.get_or_insert(e); }
+                        }
+                    }
+
+                    if let Some(e) = first_error {
+                        return Err(e);
+                    }
+                    return Ok(if changed { ExitCode::SUCCESS } else { ExitCode::from(UNCHANGED_EXIT_CODE) });
+                }
+
+                thread::scope(|scope| {
+                    let handles: Vec<_> = tasks
+                        .into_iter()
+                        .map(|task| scope.spawn(move || task.run(backend, restore_rotation)))
+                        .collect();
+
+                    // Alle Threads laufen bis zum Ende weiter; der erste Fehler wird zurückgegeben.
+                    handles
+                        .into_iter()
+                        .map(|handle| handle.join().unwrap_or_else(|_| Err(anyhow!("Thread wurde abgebrochen"))))
+                        .fold(Ok(()), Result::and)
+                })?;
+                Ok(ExitCode::SUCCESS)
             }
 
             Commands::RotateImage { image_path: _, fullscreen, background_color } => {
                 rotate_image::run_image_stabilizer(
-                    fullscreen,
-                    background_color.unwrap(),
+                    ImageSettings {
+                        fullscreen,
+                        background_color: background_color.unwrap(),
+                        flat_threshold_degrees,
+                        motion: image_motion,
+                    },
                     &image_path?,
                     orientations,
                     serial_reader,
-                )
+                    signals,
+                )?;
+                Ok(ExitCode::SUCCESS)
             }
+
+            Commands::Lock { .. } => unreachable!("wurde bereits zu Beginn ausgeführt"),
         }
     }
 
+    /// Führt eine Aktion des Befehls [`Commands::Lock`] aus und gibt den neuen Zustand aus.
+    fn run_lock_action(action: LockAction, lock: &RotationLock) -> Result<()> {
+        match action {
+            LockAction::Toggle => { lock.toggle()?; }
+            LockAction::On => lock.set_locked(true)?,
+            LockAction::Off => lock.set_locked(false)?,
+            LockAction::Status => {}
+        }
+
+        println!("Rotation {}", if lock.is_locked() { "gesperrt" } else { "entsperrt" });
+        Ok(())
+    }
+
     /// Zeigt alle verfügbaren Bildschirme an und erlaubt die interaktive Auswahl eines davon.
     /// Auf Wunsch werden detaillierte Informationen zu den Displays angezeigt.
-    fn select_monitor() -> Result<PlasmaMonitor> {
+    fn select_monitor(backend: &DisplayBackend) -> Result<PlasmaMonitor> {
         loop {
-            let mut monitors = monitor::PlasmaMonitor::list()?;
+            let mut monitors = monitor::PlasmaMonitor::list(backend)?;
 
             let i = dialoguer::Select::new()
                 .with_prompt("Monitor auswählen")
@@ -335,7 +900,7 @@ impl Args {
                 .interact()?;
 
             if i == 0 {
-                monitor::PlasmaMonitor::show_details()?;
+                monitor::PlasmaMonitor::show_details(backend)?;
             } else {
                 return Ok(monitors.swap_remove(i-1));
             }
@@ -371,71 +936,215 @@ impl Args {
         Ok(PathBuf::from(path))
     }
 
-    /// Misst interaktiv die Beschleunigungen bei den Rotationen `down` und `left`
-    /// und ruft [`OrientationVectors::from_user_input`] auf, um die Richtungsvektoren zu berechnen.
-    fn calculate_vectors(serial_reader: &mut SerialReader, monitor: Option<&PlasmaMonitor>) -> Result<OrientationVectors> {
-        // Ein Mutex wird benötigt, um Daten zwischen Threads zu teilen.
-        let acceleration_mutex = Mutex::new((Vec3::default(), false));
+    /// Misst interaktiv die Beschleunigungen in mehreren Lagen und berechnet daraus die Richtungsvektoren
+    /// (siehe [`measure_poses`](Self::measure_poses)).
+    ///
+    /// Die Bildschirme werden anschließend in ihre ursprüngliche Rotation zurückgedreht,
+    /// auch wenn die Kalibrierung abgebrochen wurde oder ein Fehler aufgetreten ist.
+    fn calculate_vectors(serial_reader: &mut SerialReader, backend: &DisplayBackend, group: Option<&mut MonitorGroup>, signals: &SignalFlags) -> Result<OrientationVectors> {
+        // Merke die aktuellen Rotationen, um sie nach der Kalibrierung wiederherstellen zu können.
+        // Ist eine davon unbekannt, wird der Bildschirm wie bisher in die Ausrichtung `none` (zuzüglich Versatz) gedreht.
+        let group = match group {
+            Some(group) => {
+                group.refresh_state(backend)?;
+                Some(&*group)
+            }
+            None => None
+        };
+        let original_rotations: Vec<Option<Rotation>> = group
+            .map(|g| g.monitors.iter().map(|m| m.current_rotation().or(Some(Rotation::None + m.offset))).collect())
+            .unwrap_or_default();
+
+        Self::with_sample_channel(serial_reader, |samples| {
+            let measurement = Self::measure_poses(samples, backend, group, signals);
+
+            // Drehe die Bildschirme wieder in die Ausgangslage.
+            // Das geschieht vor der Auswertung der Messung, damit die Bildschirme auch nach einem Abbruch nicht gedreht bleiben.
+            let restored = match group {
+                Some(group) => group.restore(backend, &original_rotations),
+                None => Ok(())
+            };
 
-        thread::scope(|s| {
-            // Hintergrundthread, der fortlaufend die Beschleunigung einliest.
-            let handle = s.spawn(|| loop {
-                let acceleration = serial_reader.next().ok_or_else(|| anyhow!("Ende des Datenstroms erreicht"))??;
-                let mut guard = acceleration_mutex.lock().unwrap();
+            let vectors = measurement?;
+            restored?;
+            Ok(vectors)
+        })
+    }
 
-                if guard.1 { return anyhow::Ok(()); }
-                guard.0 = acceleration;
-            });
+    /// Misst interaktiv die sechs Lagen, in denen jeweils eine Achse des Sensors nach oben bzw. unten zeigt,
+    /// und berechnet daraus Nullpunkt und Skalierung der Achsen (siehe [`SensorCalibration::from_axis_poses`]).
+    /// Die Messwerte müssen dafür unkorrigiert sein.
+    ///
+    /// Gibt einen Fehler zurück, wenn der Benutzer die Kalibrierung abbricht oder eine Prüfung fehlschlägt.
+    fn calibrate_sensor(serial_reader: &mut SerialReader, signals: &SignalFlags) -> Result<SensorCalibration> {
+        serial_reader.set_calibration(None);
+
+        Self::with_sample_channel(serial_reader, |samples| {
+            let cross_axis = dialoguer::Confirm::new()
+                .with_prompt("Auch die Querempfindlichkeit zwischen den Achsen ausgleichen?")
+                .default(false)
+                .interact()?;
 
-            let acc_down = {
-                let cont = dialoguer::Confirm::new()
-                    .with_prompt("Bildschirm nach unten drehen. Fortfahren?")
-                    .default(true)
-                    .wait_for_newline(true)
-                    .interact()?;
-
-                if !cont { process::exit(1); }
-                // Lese die aktuelle Beschleunigung aus dem Mutex.
-                let guard = acceleration_mutex.lock().unwrap();
-                guard.0
-            };
+            let mut poses = [[Vec3::ZERO; 2]; 3];
+            for (axis, pair) in ["x", "y", "z"].iter().zip(&mut poses) {
+                pair[0] = Self::confirm_and_measure(samples, signals, &format!("Sensor so drehen, dass die {axis}-Achse senkrecht nach oben zeigt"))?;
+                pair[1] = Self::confirm_and_measure(samples, signals, &format!("Sensor so drehen, dass die {axis}-Achse senkrecht nach unten zeigt"))?;
+            }
 
-            let acc_left = {
-                // Wenn ein Monitor angegeben ist, wird dieser nach links gedreht,
-                // um die korrekte Drehrichtung zu verdeutlichen.
-                if let Some(monitor) = monitor {
-                    monitor.rotate(Rotation::Left)?;
-                }
+            let calibration = SensorCalibration::from_axis_poses(&poses, cross_axis)?;
+            println!(
+                "Nullpunkt [{:.3}, {:.3}, {:.3}] m/s², Skalierung [{:.3}, {:.3}, {:.3}]",
+                calibration.offset.x, calibration.offset.y, calibration.offset.z,
+                calibration.scale.x, calibration.scale.y, calibration.scale.z,
+            );
+
+            let mut report = QualityReport::default();
+            report.check_sensor_calibration(&calibration);
+            report.print();
+            if report.worst() == CheckStatus::Fail {
+                bail!("Kalibrierung des Sensors fehlgeschlagen, die Korrektur wird nicht übernommen");
+            }
 
-                let cont = dialoguer::Confirm::new()
-                    .with_prompt("Bildschirm nach links drehen. Fortfahren?")
-                    .default(true)
-                    .wait_for_newline(true)
-                    .interact()?;
-
-                if !cont { process::exit(1); }
-                // Lese die aktuelle Beschleunigung aus dem Mutex und signalisiere dem Hintergrundthread, dass er anhalten soll.
-                let mut guard = acceleration_mutex.lock().unwrap();
-                guard.1 = true;
-                guard.0
-            };
+            Ok(calibration)
+        })
+    }
 
-            // Drehe den Monitor wieder in die Ausgangslage.
-            if let Some(monitor) = monitor {
-                monitor.rotate(Rotation::None)?;
-            }
+    /// Liest im Hintergrund fortlaufend Messwerte ein und reicht sie über einen Kanal an `measure` weiter,
+    /// damit während einer Eingabe keine veralteten Werte im seriellen Puffer auflaufen.
+    fn with_sample_channel<T>(serial_reader: &mut SerialReader, measure: impl FnOnce(&Receiver<Result<Vec3>>) -> Result<T>) -> Result<T> {
+        let (sender, receiver) = mpsc::channel();
+
+        thread::scope(|s| {
+            // Der Hintergrundthread endet, sobald der Empfänger nicht mehr existiert.
+            let handle = s.spawn(move || loop {
+                let acceleration = serial_reader.next().ok_or_else(|| anyhow!("Ende des Datenstroms erreicht"))?;
+                let failed = acceleration.is_err();
+
+                if sender.send(acceleration).is_err() { return anyhow::Ok(()); }
+                if failed { return Ok(()); }
+            });
 
-            // Berechne die Richtungsvektoren.
-            let vectors = OrientationVectors::from_user_input(acc_down, acc_left);
+            let result = measure(&receiver);
 
+            // Signalisiere dem Hintergrundthread, dass er anhalten soll.
+            drop(receiver);
+
+            let value = result?;
             match handle.join() {
-                Ok(Ok(())) => Ok(vectors),
+                Ok(Ok(())) => Ok(value),
                 Ok(Err(e)) => Err(e),
                 Err(panic) => std::panic::panic_any(panic),
             }
         })
     }
 
+    /// Lässt den Benutzer die Art der Kalibrierung wählen, fordert ihn auf, den Bildschirm in die
+    /// jeweiligen Lagen zu drehen, und misst dort die Beschleunigung, sobald der Sensor ruhig ist
+    /// (siehe [`calibration::measure_stable_pose`]).
+    /// - Schnell: `none` und `left`; die übrigen Vektoren werden mit [`OrientationVectors::from_user_input`] abgeleitet.
+    /// - Genau: alle vier Ausrichtungen, optional zusätzlich flach liegend;
+    ///   die Vektoren werden mit [`calibration::fit_orientation_vectors`] angepasst.
+    ///
+    /// Anschließend wird eine Zusammenfassung der Prüfungen ausgegeben (siehe [`QualityReport`]).
+    /// Gibt einen Fehler zurück, wenn der Benutzer die Kalibrierung abbricht oder eine Prüfung fehlschlägt.
+    fn measure_poses(samples: &Receiver<Result<Vec3>>, backend: &DisplayBackend, group: Option<&MonitorGroup>, signals: &SignalFlags) -> Result<OrientationVectors> {
+        let mode = dialoguer::Select::new()
+            .with_prompt("Art der Kalibrierung")
+            .item("Schnell: nach unten und links drehen")
+            .item("Genau: alle vier Ausrichtungen, Ausgleichsrechnung")
+            .item("Genau: alle vier Ausrichtungen und flach liegend, Ausgleichsrechnung")
+            .default(0)
+            .interact()?;
+
+        let rotations: &[Rotation] = match mode {
+            0 => &[Rotation::None, Rotation::Left],
+            _ => &[Rotation::None, Rotation::Right, Rotation::Inverted, Rotation::Left],
+        };
+
+        let mut measured = BTreeMap::new();
+        let mut previous = group.and_then(MonitorGroup::current_rotation);
+        for &rotation in rotations {
+            // Wenn Bildschirme angegeben sind, werden sie wie bei der automatischen Rotation
+            // (also einschließlich ihres Versatzes) gedreht, um die korrekte Drehrichtung zu verdeutlichen.
+            if let Some(group) = group {
+                group.rotate(backend, rotation, previous)?;
+                previous = Some(rotation);
+            }
+
+            let direction = match rotation {
+                Rotation::None => "nach unten",
+                Rotation::Right => "nach rechts",
+                Rotation::Inverted => "auf den Kopf",
+                Rotation::Left => "nach links",
+            };
+            measured.insert(rotation, Self::confirm_and_measure(samples, signals, &format!("Bildschirm {direction} drehen"))?);
+        }
+
+        let flat = match mode {
+            2 => Some((
+                Self::confirm_and_measure(samples, signals, "Bildschirm flach hinlegen, Anzeige nach oben")?,
+                Self::confirm_and_measure(samples, signals, "Bildschirm flach hinlegen, Anzeige nach unten")?,
+            )),
+            _ => None,
+        };
+
+        let mut report = QualityReport::default();
+        report.check_measurements(&measured, flat);
+
+        let (vectors, residual_max_angle) = if mode == 0 {
+            (OrientationVectors::from_user_input(measured[&Rotation::None], measured[&Rotation::Left]), None)
+        } else {
+            match calibration::fit_orientation_vectors(&measured, flat) {
+                Ok(fit) => {
+                    println!(
+                        "Ausgleichsrechnung: Restfehler {:.3} m/s², Erdbeschleunigung in der Ebene {:.2} m/s², Versatz [{:.2}, {:.2}, {:.2}]",
+                        fit.residual_rms, fit.gravity, fit.offset.x, fit.offset.y, fit.offset.z,
+                    );
+                    (fit.vectors, Some(fit.residual_max_angle))
+                }
+                Err(e) => {
+                    report.push(CheckStatus::Fail, "Ausgleichsrechnung", e.to_string());
+                    report.print();
+                    bail!("Kalibrierung fehlgeschlagen, die Richtungsvektoren werden nicht übernommen");
+                }
+            }
+        };
+
+        // Unbrauchbare Vektoren würden die Rotation verhindern oder NaN in die Konfiguration schreiben.
+        report.check_vectors(&vectors, residual_max_angle);
+        report.print();
+        if report.worst() == CheckStatus::Fail {
+            bail!("Kalibrierung fehlgeschlagen, die Richtungsvektoren werden nicht übernommen");
+        }
+
+        Ok(vectors)
+    }
+
+    /// Fordert den Benutzer auf, den Bildschirm in eine Lage zu bringen, und misst anschließend die Beschleunigung.
+    /// Gibt einen Fehler zurück, wenn der Benutzer die Kalibrierung abbricht oder das Programm beendet wird.
+    fn confirm_and_measure(samples: &Receiver<Result<Vec3>>, signals: &SignalFlags, instruction: &str) -> Result<Vec3> {
+        let cont = dialoguer::Confirm::new()
+            .with_prompt(format!("{instruction}. Fortfahren?"))
+            .default(true)
+            .wait_for_newline(true)
+            .interact()?;
+
+        if !cont { bail!("Kalibrierung abgebrochen"); }
+        Self::measure_pose(samples, signals)
+    }
+
+    /// Misst die Beschleunigung einer Lage und gibt Mittelwert und Streuung aus.
+    fn measure_pose(samples: &Receiver<Result<Vec3>>, signals: &SignalFlags) -> Result<Vec3> {
+        let measurement = calibration::measure_stable_pose(samples, signals)?;
+        println!(
+            "Gemessen: [{:.2}, {:.2}, {:.2}] (Betrag {:.2} m/s², Rauschen {:.3} m/s²)",
+            measurement.mean.x, measurement.mean.y, measurement.mean.z,
+            measurement.mean.length(), measurement.noise,
+        );
+
+        Ok(measurement.mean)
+    }
+
     /// Bietet an, geänderte Konfigurationswerte in der angegebenen Datei zu speichern.
     /// Wenn kein Dateipfad vorliegt, wird dieser interaktiv abgefragt.
     fn save_config(config: ConfigSettings, path: &Option<PathBuf>) -> Result<()> {
@@ -471,8 +1180,14 @@ impl Args {
     }
 
     /// Gibt einen Fehler zurück, wenn die Rotation des Displays nicht unterstützt ist.
-    /// Unterstützt wird ausschließlich die Desktop-Umgebung `Plasma` von KDE unter Linux.
-    fn check_rotation_supported() -> Result<()> {
+    /// Ohne Befehlsvorlagen wird ausschließlich die Desktop-Umgebung `Plasma` von KDE unter Linux unterstützt.
+    fn check_rotation_supported(backend: &DisplayBackend) -> Result<()> {
+        // Bei benutzerdefinierten Befehlen ist der Benutzer selbst für die Unterstützung verantwortlich.
+        // Im Probelauf wird nichts rotiert.
+        if let BackendKind::Custom(_) | BackendKind::DryRun = backend.kind {
+            return Ok(());
+        }
+
         // CFGs (Compiler Flags) können verwendet werden, um während der Kompilierung das verwendete Betriebssystem zu untersuchen
 
         #[cfg(not(target_os = "linux"))]
@@ -0,0 +1,226 @@
+//! Programme, über die Bildschirme aufgelistet und rotiert werden:
+//! `kscreen-doctor` unter KDE Plasma oder benutzerdefinierte Befehlsvorlagen für andere Desktop-Umgebungen.
+//! Im Probelauf wird stattdessen gar kein Programm aufgerufen.
+
+use std::{process::Command, sync::Mutex};
+
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{command::CommandSettings, monitor::{PlasmaMonitor, Transform}};
+
+
+/// Verhindert, dass mehrere Sensoren gleichzeitig Bildschirme abfragen oder rotieren.
+/// Parallele Aufrufe von `kscreen-doctor` können sich gegenseitig überschreiben.
+static DISPLAY_COMMAND_LOCK: Mutex<()> = Mutex::new(());
+
+
+/// Befehlsvorlagen für Desktop-Umgebungen, die nicht direkt unterstützt werden.
+///
+/// Jede Vorlage wird an Leerzeichen in einzelne Argumente aufgeteilt.
+/// Anschließend werden in jedem Argument folgende Platzhalter ersetzt:
+/// - `{name}`: Name des Bildschirms
+/// - `{rotation}`: Rotation als `none`, `left`, `right` oder `inverted`
+/// - `{degrees}`: Rotation im Uhrzeigersinn als `0`, `90`, `180` oder `270`
+/// - `{xrandr}`: Rotation als `normal`, `left`, `right` oder `inverted`
+/// - `{reflect}`: Spiegelung als `normal` oder `x` (für `xrandr --reflect`)
+///
+/// Ist eine [`Mirror`](crate::monitor::Mirror)-Spiegelung konfiguriert, beziehen sich die Rotationsplatzhalter
+/// auf die Rotation der gespiegelten [`Transform`].
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CommandTemplates {
+    /// Befehl, der die Namen aller Bildschirme zeilenweise ausgibt.
+    /// Ohne diesen Befehl muss der Bildschirm per Eingabeargument oder Konfiguration angegeben werden.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub list: Option<String>,
+
+    /// Befehl, der einen Bildschirm rotiert, z.B. `my-tool --output {name} --rotate {rotation}`.
+    pub rotate: String,
+}
+
+impl CommandTemplates {
+    /// Erzeugt einen [`Command`] aus der Vorlage, indem alle Platzhalter ersetzt werden.
+    /// Ohne Bildschirmname bzw. Transformation werden die entsprechenden Platzhalter nicht ersetzt.
+    pub fn build_command(template: &str, name: Option<&str>, transform: Option<Transform>) -> Result<Command> {
+        let mut args = template.split_whitespace().map(|arg| {
+            let mut arg = arg.to_string();
+
+            if let Some(name) = name {
+                arg = arg.replace("{name}", name);
+            }
+
+            if let Some(transform) = transform {
+                let rotation = transform.rotation;
+                arg = arg
+                    .replace("{rotation}", rotation.to_str())
+                    .replace("{degrees}", &rotation.to_degrees().to_string())
+                    .replace("{xrandr}", rotation.to_xrandr_str())
+                    .replace("{reflect}", transform.to_xrandr_reflect_str());
+            }
+
+            arg
+        });
+
+        let program = args.next().ok_or_else(|| anyhow!("Leere Befehlsvorlage"))?;
+        let mut command = Command::new(program);
+        command.args(args);
+
+        Ok(command)
+    }
+
+    /// Gibt die Vorlage zum Auflisten der Bildschirme zurück oder einen Fehler, wenn keine konfiguriert ist.
+    pub fn list_template(&self) -> Result<&str> {
+        self.list.as_deref().ok_or_else(|| anyhow!("Kein Befehl zum Auflisten der Bildschirme konfiguriert"))
+    }
+}
+
+
+/// Legt fest, über welches Programm Bildschirme aufgelistet und rotiert werden
+/// und wie diese Aufrufe ausgeführt werden.
+#[derive(Clone)]
+pub struct DisplayBackend {
+    pub kind: BackendKind,
+
+    /// Zeitlimit, Wiederholungen und Fehlerbehandlung der Aufrufe
+    pub commands: CommandSettings,
+}
+
+/// Programm, über das Bildschirme aufgelistet und rotiert werden.
+#[derive(Clone)]
+pub enum BackendKind {
+    /// `kscreen-doctor` von KDE Plasma
+    KScreenDoctor,
+
+    /// Benutzerdefinierte Befehle aus der Konfigurationsdatei
+    Custom(CommandTemplates),
+
+    /// Probelauf (`--dry-run`): Es werden keine Befehle ausgeführt,
+    /// stattdessen protokolliert [`run_automatic_rotation`](crate::monitor::run_automatic_rotation) jede Entscheidung.
+    DryRun,
+}
+
+impl DisplayBackend {
+    /// Gibt den Namen des aufgerufenen Programms für Fehlermeldungen zurück.
+    fn program_name(&self) -> &str {
+        match self.kind {
+            BackendKind::KScreenDoctor => "kscreen-doctor",
+            BackendKind::Custom(_) => "Benutzerdefinierter Befehl",
+            BackendKind::DryRun => "Probelauf",
+        }
+    }
+
+    /// Gibt an, ob sich der Zustand der Bildschirme über dieses Backend abfragen lässt.
+    pub fn reports_state(&self) -> bool {
+        matches!(self.kind, BackendKind::KScreenDoctor)
+    }
+
+    /// Gibt an, ob sich die vorhandenen Bildschirme über dieses Backend auflisten lassen.
+    pub fn can_list(&self) -> bool {
+        match &self.kind {
+            BackendKind::KScreenDoctor => true,
+            BackendKind::Custom(templates) => templates.list.is_some(),
+            BackendKind::DryRun => false,
+        }
+    }
+
+    /// Gibt an, ob es sich um einen Probelauf handelt.
+    pub fn is_dry_run(&self) -> bool {
+        matches!(self.kind, BackendKind::DryRun)
+    }
+
+    /// Gibt das Backend zurück, über das der aktuelle Zustand der Bildschirme gelesen wird.
+    /// Im Probelauf ist das `kscreen-doctor` ohne Wiederholungen, da dabei nichts verändert wird;
+    /// ansonsten ist es dieses Backend selbst.
+    pub fn state_query(&self) -> DisplayBackend {
+        match self.kind {
+            BackendKind::DryRun => DisplayBackend {
+                kind: BackendKind::KScreenDoctor,
+                commands: CommandSettings { retries: Some(0), ..self.commands.clone() },
+            },
+            _ => self.clone(),
+        }
+    }
+
+    /// Führt einen Befehl mit den konfigurierten Zeitlimits und Wiederholungen aus.
+    /// Aufrufe aus mehreren Threads werden über [`DISPLAY_COMMAND_LOCK`] nacheinander ausgeführt.
+    pub fn run(&self, command: &mut Command, capture_stdout: bool) -> Result<Vec<u8>> {
+        self.commands.run_exclusive(&DISPLAY_COMMAND_LOCK, command, self.program_name(), capture_stdout)
+    }
+
+    /// Prüft, ob sich der Bildschirm mit diesem Backend wie konfiguriert rotieren lässt.
+    /// Eine Spiegelung erfordert bei Befehlsvorlagen den Platzhalter `{reflect}`,
+    /// da die Rotationsplatzhalter sonst eine gespiegelte Transformation ohne die Spiegelung selbst erhalten würden.
+    pub fn validate_monitor(&self, monitor: &PlasmaMonitor) -> Result<()> {
+        self.validate_name(&monitor.name)?;
+
+        if let BackendKind::Custom(templates) = &self.kind && monitor.mirror.is_some() && !templates.rotate.contains("{reflect}") {
+            bail!("Für den gespiegelten Bildschirm \"{}\" muss die Befehlsvorlage zum Rotieren den Platzhalter {{reflect}} enthalten", monitor.name);
+        }
+
+        Ok(())
+    }
+
+    /// Prüft, ob sich der Name gefahrlos in die Befehle einsetzen lässt.
+    /// Leerzeichen würden die Aufteilung der Befehlsvorlagen zerstören und geschweifte Klammern deren Platzhalter.
+    /// Punkte sind nur für `kscreen-doctor` unzulässig, da sie dessen Syntax `output.NAME.rotation` zerstören würden.
+    fn validate_name(&self, name: &str) -> Result<()> {
+        if name.is_empty() {
+            bail!("Leerer Bildschirmname");
+        }
+
+        let kscreen_doctor = matches!(self.kind, BackendKind::KScreenDoctor);
+        let invalid = |c: &char| c.is_whitespace() || c.is_control() || matches!(c, '{' | '}') || (kscreen_doctor && *c == '.');
+
+        if let Some(c) = name.chars().find(invalid) {
+            bail!("Ungültiges Zeichen {c:?} im Bildschirmnamen \"{name}\"");
+        }
+
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::monitor::Mirror;
+
+    use super::*;
+
+    fn backend(kind: BackendKind) -> DisplayBackend {
+        DisplayBackend { kind, commands: CommandSettings::default() }
+    }
+
+    #[test]
+    fn dots_are_only_rejected_for_kscreen_doctor() {
+        let custom = backend(BackendKind::Custom(CommandTemplates { list: None, rotate: "true".to_string() }));
+
+        assert!(backend(BackendKind::KScreenDoctor).validate_name("DP.1").is_err());
+        assert!(custom.validate_name("DP.1").is_ok());
+
+        for name in ["", "DP 1", "DP-{name}", "DP\t1"] {
+            assert!(custom.validate_name(name).is_err(), "{name:?}");
+        }
+    }
+
+    #[test]
+    fn mirrored_monitors_require_reflect_placeholder() {
+        let templates = |rotate: &str| BackendKind::Custom(CommandTemplates { list: None, rotate: rotate.to_string() });
+        let mirrored = PlasmaMonitor { mirror: Some(Mirror::Vertical), ..PlasmaMonitor::from_name("DP-1".to_string()) };
+        let plain = PlasmaMonitor::from_name("DP-1".to_string());
+
+        assert!(backend(templates("xrandr --output {name} --rotate {xrandr}")).validate_monitor(&mirrored).is_err());
+        assert!(backend(templates("xrandr --output {name} --rotate {xrandr}")).validate_monitor(&plain).is_ok());
+        assert!(backend(templates("xrandr --output {name} --rotate {xrandr} --reflect {reflect}")).validate_monitor(&mirrored).is_ok());
+        assert!(backend(BackendKind::KScreenDoctor).validate_monitor(&mirrored).is_ok());
+    }
+
+    #[test]
+    fn dry_run_reads_state_from_kscreen_doctor() {
+        let query = backend(BackendKind::DryRun).state_query();
+        assert!(query.reports_state());
+        assert_eq!(query.commands.retries, Some(0));
+
+        let custom = backend(BackendKind::Custom(CommandTemplates { list: None, rotate: "true".to_string() }));
+        assert!(!custom.state_query().reports_state());
+    }
+}
@@ -0,0 +1,722 @@
+//! Messung der Beschleunigung für die Kalibrierung des Sensors.
+//!
+//! Ein einzelner Messwert ist ungeeignet, da der Bildschirm kurz nach dem Drehen noch nachschwingt
+//! und der Sensor zudem rauscht. Daher wird über ein Zeitfenster gemessen
+//! und die Messung erst übernommen, wenn die Werte darin nur noch gering streuen.
+//! Gespeichert wird dann der Mittelwert des Fensters.
+//!
+//! Aus den Messungen aller vier Ausrichtungen (und optional der flach liegenden Lagen)
+//! werden die Richtungsvektoren per Ausgleichsrechnung bestimmt (siehe [`fit_orientation_vectors`]).
+//!
+//! Unabhängig davon können Nullpunkt und Skalierung der Achsen des Sensors selbst kalibriert werden
+//! (siehe [`SensorCalibration`]); günstige ADXL345-Platinen haben deutliche Nullpunktfehler.
+//!
+//! Vor dem Übernehmen wird jede Kalibrierung geprüft (siehe [`QualityReport`]),
+//! damit z.B. ein vergessenes Drehen des Bildschirms nicht zu unbrauchbaren Vektoren in der Konfiguration führt.
+
+use std::{collections::{BTreeMap, VecDeque}, f32::consts::PI, fmt::{self, Display}, io::{self, ErrorKind, Write}, sync::mpsc::{Receiver, RecvTimeoutError}, time::{Duration, Instant}};
+
+use anyhow::{Result, anyhow, bail};
+use glam::{Mat3, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::{monitor::{OrientationVectors, Rotation}, signals::SignalFlags};
+
+
+/// Anzahl der Messwerte, über die gemittelt wird (bei 10 Messwerten pro Sekunde etwa 2 Sekunden).
+const WINDOW_SAMPLES: usize = 20;
+
+/// Maximale Streuung der Messwerte im Zeitfenster in m/s², bei der die Messung übernommen wird.
+const MAX_NOISE: f32 = 0.2;
+
+/// Zeit, nach der die Messung abgebrochen wird, wenn der Sensor nicht zur Ruhe kommt.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Höchstens so lange wird auf einen Messwert gewartet, bevor erneut auf `SIGINT` und `SIGTERM` geprüft wird.
+const SIGNAL_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Sinus des Winkels, unterhalb dessen zwei geschätzte Achsen als parallel gelten.
+const DEGENERATE_SINE: f32 = 1e-3;
+
+/// Normwert der Erdbeschleunigung in m/s².
+const STANDARD_GRAVITY: f32 = 9.80665;
+
+/// Relative Abweichung des Betrags einer Messung von 1 g, ab der gewarnt wird bzw. die Kalibrierung fehlschlägt.
+const MAGNITUDE_LIMITS: (f32, f32) = (0.1, 0.3);
+
+/// Abweichung des Winkels zwischen zwei Lagen vom Sollwert (90° bzw. 180°) in Grad,
+/// ab der gewarnt wird bzw. die Kalibrierung fehlschlägt.
+const POSE_ANGLE_LIMITS: (f32, f32) = (15.0, 45.0);
+
+/// Größter Winkel zwischen Messung und Modell in Grad, ab dem gewarnt wird bzw. die Kalibrierung fehlschlägt.
+const RESIDUAL_ANGLE_LIMITS: (f32, f32) = (5.0, 15.0);
+
+/// Abweichung der Vektoren von `none` und `right` von 90° in Grad, ab der gewarnt wird bzw. die Kalibrierung fehlschlägt.
+/// Die Bildstabilisierung setzt voraus, dass beide senkrecht zueinander stehen.
+const ORTHOGONALITY_LIMITS: (f32, f32) = (5.0, 15.0);
+
+/// Nullpunktfehler einer Achse in m/s², ab dem gewarnt wird bzw. die Kalibrierung des Sensors fehlschlägt.
+const SENSOR_OFFSET_LIMITS: (f32, f32) = (1.0, 3.0);
+
+/// Relative Abweichung der Skalierung einer Achse von 1, ab der gewarnt wird bzw. die Kalibrierung des Sensors fehlschlägt.
+const SENSOR_SCALE_LIMITS: (f32, f32) = (0.1, 0.3);
+
+/// Maximale Anzahl an Iterationen bei der Berechnung der nächstgelegenen orthogonalen Matrix.
+const MAX_POLAR_ITERATIONS: usize = 50;
+
+
+/// Ergebnis der Messung einer Lage des Bildschirms.
+#[derive(Clone, Copy)]
+pub struct PoseMeasurement {
+    /// Mittelwert der Messwerte im Zeitfenster
+    pub mean: Vec3,
+
+    /// Streuung der Messwerte um den Mittelwert in m/s² (Wurzel der mittleren quadratischen Abweichung)
+    pub noise: f32,
+}
+
+impl PoseMeasurement {
+    /// Berechnet Mittelwert und Streuung der angegebenen Messwerte.
+    fn of(samples: &VecDeque<Vec3>) -> Self {
+        let count = samples.len() as f32;
+        let mean = samples.iter().sum::<Vec3>() / count;
+        let variance = samples.iter().map(|acc| acc.distance_squared(mean)).sum::<f32>() / count;
+
+        Self { mean, noise: variance.sqrt() }
+    }
+}
+
+
+/// Liest Messwerte, bis sie über das gesamte Zeitfenster nur gering streuen, und gibt deren Mittelwert zurück.
+/// Der Fortschritt wird fortlaufend in einer Zeile ausgegeben.
+///
+/// Messwerte, die vor dem Aufruf empfangen wurden, werden verworfen,
+/// da der Bildschirm zu diesem Zeitpunkt möglicherweise noch gedreht wurde.
+/// Ein Fehler unter ihnen wird dagegen sofort zurückgegeben.
+///
+/// Wird das Programm währenddessen beendet, wird ein Fehler der Art [`ErrorKind::Interrupted`] zurückgegeben,
+/// auch wenn keine Messwerte mehr eintreffen.
+pub fn measure_stable_pose(samples: &Receiver<Result<Vec3>>, signals: &SignalFlags) -> Result<PoseMeasurement> {
+    while let Ok(sample) = samples.try_recv() {
+        sample?;
+    }
+
+    let deadline = Instant::now() + TIMEOUT;
+    let mut window = VecDeque::with_capacity(WINDOW_SAMPLES);
+    let mut noise = None;
+
+    loop {
+        if signals.should_terminate() {
+            eprintln!();
+            return Err(io::Error::new(ErrorKind::Interrupted, "Kalibrierung abgebrochen").into());
+        }
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let acc = match samples.recv_timeout(remaining.min(SIGNAL_POLL_INTERVAL)) {
+            Ok(acc) => acc?,
+            Err(RecvTimeoutError::Timeout) if !remaining.is_zero() => continue,
+            Err(RecvTimeoutError::Timeout) => {
+                eprintln!();
+                bail!(
+                    "Der Sensor ist innerhalb von {} s nicht zur Ruhe gekommen (Streuung zuletzt {})",
+                    TIMEOUT.as_secs(),
+                    noise.map_or("unbekannt".to_string(), |n| format!("{n:.3} m/s²")),
+                );
+            }
+            Err(RecvTimeoutError::Disconnected) => return Err(anyhow!("Ende des Datenstroms erreicht")),
+        };
+
+        if window.len() == WINDOW_SAMPLES {
+            window.pop_front();
+        }
+        window.push_back(acc);
+
+        let measurement = PoseMeasurement::of(&window);
+        noise = Some(measurement.noise);
+
+        eprint!(
+            "\rMessung: {:>2}/{WINDOW_SAMPLES} Messwerte, Streuung {:.3} m/s²   ",
+            window.len(), measurement.noise,
+        );
+        let _ = std::io::stderr().flush();
+
+        if window.len() == WINDOW_SAMPLES && measurement.noise <= MAX_NOISE {
+            eprintln!();
+            return Ok(measurement);
+        }
+    }
+}
+
+
+/// Ergebnis der Ausgleichsrechnung in [`fit_orientation_vectors`].
+pub struct OrientationFit {
+    /// Angepasste Richtungsvektoren der vier Ausrichtungen mit Mittelpunkt und Normale der Rotationsebene
+    pub vectors: OrientationVectors,
+
+    /// Betrag der Erdbeschleunigung in der Rotationsebene in m/s²; bei geneigter Halterung entsprechend kleiner
+    pub gravity: f32,
+
+    /// Mittelpunkt der Rotation, z.B. durch eine nach hinten geneigte Halterung oder einen Nullpunktfehler
+    pub offset: Vec3,
+
+    /// Wurzel der mittleren quadratischen Abweichung zwischen Messung und Modell in m/s²
+    pub residual_rms: f32,
+
+    /// Größter Winkel zwischen einer Messung und dem zugehörigen Modellvektor im Bogenmaß,
+    /// jeweils vom Mittelpunkt der zugehörigen Lagen aus gesehen
+    pub residual_max_angle: f32,
+}
+
+/// Bestimmt die Richtungsvektoren aus den Messungen aller vier Ausrichtungen per Ausgleichsrechnung.
+/// `flat` enthält optional die Messungen bei flach liegendem Bildschirm (Anzeige nach oben, nach unten).
+///
+/// Modell: Jede Messung setzt sich aus einem gemeinsamen Versatz und der um eine Vierteldrehung
+/// weitergedrehten Erdbeschleunigung zusammen. Die Drehachsen bilden ein orthogonales Dreibein,
+/// das gegenüber den Achsen des Sensors beliebig verdreht sein darf.
+/// Versatz, Betrag und Dreibein werden so gewählt, dass die Summe der quadratischen Abweichungen minimal ist:
+/// - Der Versatz ist der Mittelwert der vier aufrechten Messungen, da sich deren Modellvektoren paarweise aufheben.
+///   Er ist der Mittelpunkt der Rotation und wird zusammen mit der Normale der Rotationsebene gespeichert.
+///   Die flach liegenden Lagen haben einen eigenen Mittelpunkt: Bei geneigter Halterung enthält der Versatz
+///   den Anteil der Erdbeschleunigung entlang der Drehachse, flach liegend dagegen nicht.
+/// - Die halben Differenzen gegenüberliegender Lagen schätzen die Achsen;
+///   das nächstgelegene orthogonale Dreibein ist der orthogonale Faktor ihrer Polarzerlegung.
+///   Anders als beim Orthogonalisieren nach Gram-Schmidt werden dabei alle Achsen gleich behandelt.
+///
+/// Ohne flach liegende Lagen ist die dritte Achse frei und wird senkrecht zu den beiden anderen gewählt.
+pub fn fit_orientation_vectors(rotations: &BTreeMap<Rotation, Vec3>, flat: Option<(Vec3, Vec3)>) -> Result<OrientationFit> {
+    let measurement = |rotation| rotations
+        .get(&rotation)
+        .copied()
+        .ok_or_else(|| anyhow!("Für die Ausrichtung {rotation} liegt keine Messung vor"));
+
+    // Gegenüberliegende Lagen: `none`/`inverted` und `left`/`right`.
+    let pairs = [
+        (measurement(Rotation::None)?, measurement(Rotation::Inverted)?),
+        (measurement(Rotation::Left)?, measurement(Rotation::Right)?),
+    ];
+
+    let offset = pairs.iter().map(|(a, b)| *a + *b).sum::<Vec3>() / 4.0;
+    let axes: Vec<Vec3> = pairs.iter().chain(&flat).map(|(a, b)| (*a - *b) / 2.0).collect();
+
+    let normal = axes[0].cross(axes[1]);
+    if normal.length() <= DEGENERATE_SINE * axes[0].length() * axes[1].length() {
+        bail!("Die Messungen von \"none\" und \"left\" liegen auf einer Geraden; wurde der Bildschirm gedreht?");
+    }
+
+    // Ohne Messung wird die dritte Achse senkrecht zu den anderen mit deren mittlerer Länge angenommen.
+    // Sie beeinflusst die ersten beiden Achsen des orthogonalen Faktors dann nicht.
+    let third = axes.get(2).copied().unwrap_or_else(|| normal.normalize() * (axes[0].length() + axes[1].length()) / 2.0);
+    let frame = nearest_orthogonal(Mat3::from_cols(axes[0], axes[1], third))?;
+
+    // Betrag der Erdbeschleunigung in der Ebene: mittlere Projektion der geschätzten Achsen auf das Dreibein.
+    let gravity = (frame.col(0).dot(axes[0]) + frame.col(1).dot(axes[1])) / 2.0;
+
+    let vectors = OrientationVectors::centered(frame.col(0) * gravity, frame.col(1) * gravity, offset, Some(frame.col(2)));
+
+    // Abweichungen zwischen Messung und Modell als Paare (Messung, Mittelpunkt, Modellvektor),
+    // einschließlich der flach liegenden Lagen.
+    let mut residuals: Vec<(Vec3, Vec3, Vec3)> = rotations.iter().map(|(r, m)| (*m, offset, vectors.vectors[r])).collect();
+    if let Some((up, down)) = flat {
+        let center = (up + down) / 2.0;
+        let normal = frame.col(2) * frame.col(2).dot(axes[2]);
+        residuals.extend([(up, center, normal), (down, center, -normal)]);
+    }
+
+    let residual_rms = (residuals.iter().map(|(m, c, v)| m.distance_squared(*c + *v)).sum::<f32>() / residuals.len() as f32).sqrt();
+    let residual_max_angle = residuals.iter().map(|(m, c, v)| (*m - *c).angle_between(*v)).fold(0.0, f32::max);
+
+    Ok(OrientationFit { vectors, gravity, offset, residual_rms, residual_max_angle })
+}
+
+/// Berechnet den orthogonalen Faktor der Polarzerlegung, d.h. die orthogonale Matrix,
+/// die der angegebenen Matrix im Sinne der Frobeniusnorm am nächsten liegt.
+///
+/// Verwendet wird das Newton-Verfahren `Q ← (Q + Q⁻ᵀ) / 2`, das für invertierbare Matrizen quadratisch konvergiert.
+fn nearest_orthogonal(matrix: Mat3) -> Result<Mat3> {
+    let volume = matrix.x_axis.length() * matrix.y_axis.length() * matrix.z_axis.length();
+    if matrix.determinant().abs() <= DEGENERATE_SINE * volume {
+        bail!("Die gemessenen Lagen spannen keinen Raum auf");
+    }
+
+    let mut q = matrix;
+    for _ in 0..MAX_POLAR_ITERATIONS {
+        let next = (q + q.inverse().transpose()) * 0.5;
+        let converged = next.abs_diff_eq(q, 1e-6);
+        q = next;
+
+        if converged {
+            break;
+        }
+    }
+
+    Ok(q)
+}
+
+
+/// Korrektur der Rohwerte des Beschleunigungssensors: `korrigiert = Skalierung · (roh − Nullpunkt)`.
+///
+/// Sie wird auf jeden Messwert angewendet, bevor er mit den Richtungsvektoren verglichen wird
+/// (siehe [`SerialReader::set_calibration`](crate::serial::SerialReader::set_calibration)).
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SensorCalibration {
+    /// Nullpunktfehler je Achse in m/s², wird vom Rohwert abgezogen
+    pub offset: Vec3,
+
+    /// Skalierungsfaktor je Achse
+    pub scale: Vec3,
+
+    /// Optionale Matrix (spaltenweise), die zusätzlich die Querempfindlichkeit zwischen den Achsen ausgleicht.
+    /// Sofern vorhanden, wird sie anstelle von `scale` verwendet.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cross_axis: Option<Mat3>,
+}
+
+impl SensorCalibration {
+    /// Berechnet die Korrektur aus den Messungen der sechs achsparallelen Lagen.
+    /// `poses[i]` enthält die Rohwerte, während die Achse `i` (x, y, z) nach oben bzw. nach unten zeigt.
+    ///
+    /// Der Nullpunkt ist der Mittelwert aller Messungen, da sich die Erdbeschleunigung gegenüberliegender Lagen aufhebt.
+    /// Die halben Differenzen gegenüberliegender Lagen bilden die Spalten der Empfindlichkeitsmatrix;
+    /// deren Kehrwert (bzw. mit `cross_axis` deren Inverse) bildet sie auf 1 g ab.
+    pub fn from_axis_poses(poses: &[[Vec3; 2]; 3], cross_axis: bool) -> Result<Self> {
+        const AXES: [&str; 3] = ["x", "y", "z"];
+
+        for (i, pair) in poses.iter().enumerate() {
+            for (measurement, sign, direction) in [(pair[0], 1.0, "oben"), (pair[1], -1.0, "unten")] {
+                if !measurement.is_finite() {
+                    bail!("Die Messung mit der {}-Achse nach {direction} enthält ungültige Werte", AXES[i]);
+                }
+
+                // Die Erdbeschleunigung muss überwiegend auf der gemessenen Achse liegen.
+                let dominant = (0..3).max_by(|&a, &b| measurement[a].abs().total_cmp(&measurement[b].abs())).unwrap();
+                if dominant != i || measurement[i].signum() != sign {
+                    bail!(
+                        "Bei der Messung mit der {}-Achse nach {direction} überwiegt die {}{}-Achse; wurde der Sensor richtig gedreht?",
+                        AXES[i], if measurement[dominant] < 0.0 { "-" } else { "+" }, AXES[dominant],
+                    );
+                }
+            }
+        }
+
+        let offset = poses.iter().flatten().sum::<Vec3>() / 6.0;
+        let sensitivity = Mat3::from_cols_array_2d(&poses.map(|[up, down]| ((up - down) / 2.0).to_array()));
+        let scale = Vec3::from_array([0, 1, 2].map(|i| STANDARD_GRAVITY / sensitivity.col(i)[i]));
+
+        let cross_axis = match cross_axis {
+            true => {
+                let volume = sensitivity.x_axis.length() * sensitivity.y_axis.length() * sensitivity.z_axis.length();
+                if sensitivity.determinant().abs() <= DEGENERATE_SINE * volume {
+                    bail!("Die gemessenen Lagen spannen keinen Raum auf");
+                }
+                Some(sensitivity.inverse() * STANDARD_GRAVITY)
+            }
+            false => None,
+        };
+
+        Ok(Self { offset, scale, cross_axis })
+    }
+
+    /// Wendet die Korrektur auf einen Rohwert an.
+    pub fn apply(&self, raw: Vec3) -> Vec3 {
+        match self.cross_axis {
+            Some(matrix) => matrix * (raw - self.offset),
+            None => (raw - self.offset) * self.scale,
+        }
+    }
+}
+
+
+/// Bewertung einer einzelnen Prüfung einer Kalibrierung.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl CheckStatus {
+    /// Bewertet einen Wert anhand der Grenzen für eine Warnung bzw. einen Fehlschlag.
+    /// Ungültige Werte (NaN) gelten als Fehlschlag.
+    fn rate(value: f32, (warn, fail): (f32, f32)) -> Self {
+        if value <= warn {
+            Self::Pass
+        } else if value <= fail {
+            Self::Warn
+        } else {
+            Self::Fail
+        }
+    }
+}
+
+impl Display for CheckStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let str = match self {
+            Self::Pass => "OK",
+            Self::Warn => "WARNUNG",
+            Self::Fail => "FEHLER",
+        };
+        // `pad` berücksichtigt die Breite, damit die Zusammenfassung bündig ausgegeben werden kann.
+        f.pad(str)
+    }
+}
+
+/// Ergebnis einer einzelnen Prüfung.
+struct QualityCheck {
+    status: CheckStatus,
+    name: &'static str,
+    detail: String,
+}
+
+/// Zusammenfassung aller Prüfungen einer Kalibrierung.
+#[derive(Default)]
+pub struct QualityReport(Vec<QualityCheck>);
+
+impl QualityReport {
+    /// Fügt das Ergebnis einer Prüfung hinzu.
+    pub fn push(&mut self, status: CheckStatus, name: &'static str, detail: String) {
+        self.0.push(QualityCheck { status, name, detail });
+    }
+
+    /// Prüft die gemessenen Lagen:
+    /// - Alle Messwerte müssen endlich sein.
+    /// - Ihr Betrag muss ungefähr der Erdbeschleunigung entsprechen.
+    /// - Benachbarte Lagen müssen etwa 90°, gegenüberliegende etwa 180° auseinanderliegen.
+    ///   Sind zwei Lagen nahezu gleich, wurde der Bildschirm vermutlich nicht gedreht.
+    ///
+    /// Die Winkel werden wie in [`fit_orientation_vectors`] vom Mittelpunkt der jeweiligen Lagen aus gemessen,
+    /// damit eine geneigte Halterung die Prüfung nicht verfälscht.
+    /// Liegen nicht alle vier Ausrichtungen vor, ist der Mittelpunkt unbekannt und es wird vom Ursprung aus gemessen.
+    pub fn check_measurements(&mut self, rotations: &BTreeMap<Rotation, Vec3>, flat: Option<(Vec3, Vec3)>) {
+        let center = match rotations.len() {
+            4 => rotations.values().sum::<Vec3>() / 4.0,
+            _ => Vec3::ZERO,
+        };
+        let flat_center = flat.map_or(Vec3::ZERO, |(up, down)| (up + down) / 2.0);
+
+        // Jede Lage mit Bezeichnung, Messung, Mittelpunkt und ihrer Sollrichtung in einem beliebigen rechtwinkligen Dreibein.
+        let mut poses: Vec<(String, Vec3, Vec3, Vec3)> = rotations
+            .iter()
+            .map(|(&r, &m)| {
+                let ideal = match r {
+                    Rotation::None => Vec3::X,
+                    Rotation::Right => Vec3::NEG_Y,
+                    Rotation::Inverted => Vec3::NEG_X,
+                    Rotation::Left => Vec3::Y,
+                };
+                (r.to_string(), m, center, ideal)
+            })
+            .collect();
+        if let Some((up, down)) = flat {
+            poses.push(("flach (oben)".to_string(), up, flat_center, Vec3::Z));
+            poses.push(("flach (unten)".to_string(), down, flat_center, Vec3::NEG_Z));
+        }
+
+        if let Some((name, ..)) = poses.iter().find(|(_, m, ..)| !m.is_finite()) {
+            self.push(CheckStatus::Fail, "Messwerte", format!("Messung \"{name}\" enthält ungültige Werte"));
+            return;
+        }
+        self.push(CheckStatus::Pass, "Messwerte", format!("{} Lagen gemessen", poses.len()));
+
+        let (name, magnitude) = poses
+            .iter()
+            .map(|(name, m, ..)| (name, m.length()))
+            .max_by(|(_, a), (_, b)| (a - STANDARD_GRAVITY).abs().total_cmp(&(b - STANDARD_GRAVITY).abs()))
+            .unwrap();
+        let deviation = (magnitude - STANDARD_GRAVITY).abs() / STANDARD_GRAVITY;
+        self.push(
+            CheckStatus::rate(deviation, MAGNITUDE_LIMITS),
+            "Betrag",
+            format!("größte Abweichung von 1 g bei \"{name}\": {magnitude:.2} m/s² ({:.0} %)", deviation * 100.0),
+        );
+
+        let worst_pair = poses
+            .iter()
+            .enumerate()
+            .flat_map(|(i, a)| poses[i + 1..].iter().map(move |b| (a, b)))
+            .map(|((name_a, a, center_a, ideal_a), (name_b, b, center_b, ideal_b))| {
+                let expected = ideal_a.angle_between(*ideal_b);
+                (name_a, name_b, (*a - *center_a).angle_between(*b - *center_b), expected)
+            })
+            .max_by(|(_, _, a, ea), (_, _, b, eb)| (a - ea).abs().total_cmp(&(b - eb).abs()));
+
+        if let Some((name_a, name_b, angle, expected)) = worst_pair {
+            let deviation = (angle - expected).abs().to_degrees();
+            self.push(
+                CheckStatus::rate(deviation, POSE_ANGLE_LIMITS),
+                "Winkel",
+                format!(
+                    "\"{name_a}\" und \"{name_b}\" liegen {:.1}° auseinander (erwartet {:.0}°)",
+                    angle.to_degrees(), expected.to_degrees(),
+                ),
+            );
+        }
+    }
+
+    /// Prüft die berechneten Richtungsvektoren, bevor sie verwendet oder gespeichert werden:
+    /// - Alle Vektoren müssen endlich und ungleich null sein.
+    /// - `none` und `left` müssen eine Rotationsebene aufspannen (siehe [`OrientationVectors::tilt_out_of_plane`]).
+    /// - `none` und `right` müssen senkrecht zueinander stehen, wie es die Bildstabilisierung voraussetzt.
+    ///
+    /// `residual_max_angle` ist der größte Winkel zwischen Messung und Modell einer Ausgleichsrechnung, sofern vorhanden.
+    pub fn check_vectors(&mut self, vectors: &OrientationVectors, residual_max_angle: Option<f32>) {
+        if let Some(angle) = residual_max_angle {
+            let degrees = angle.to_degrees();
+            self.push(
+                CheckStatus::rate(degrees, RESIDUAL_ANGLE_LIMITS),
+                "Ausgleichsrechnung",
+                format!("größte Abweichung zwischen Messung und Modell {degrees:.1}°"),
+            );
+        }
+
+        if let Some((rotation, _)) = vectors.vectors.iter().find(|(_, v)| !v.is_finite() || **v == Vec3::ZERO) {
+            self.push(CheckStatus::Fail, "Richtungsvektoren", format!("Vektor für \"{rotation}\" ist ungültig"));
+            return;
+        }
+
+        let none = vectors.vectors[&Rotation::None];
+        let left = vectors.vectors[&Rotation::Left];
+        if none.cross(left).length() <= DEGENERATE_SINE * none.length() * left.length() {
+            self.push(CheckStatus::Fail, "Richtungsvektoren", "\"none\" und \"left\" spannen keine Ebene auf".to_string());
+            return;
+        }
+
+        let deviation = (none.angle_between(vectors.vectors[&Rotation::Right]) - PI / 2.0).abs().to_degrees();
+        self.push(
+            CheckStatus::rate(deviation, ORTHOGONALITY_LIMITS),
+            "Richtungsvektoren",
+            format!("\"none\" und \"right\" weichen {deviation:.1}° vom rechten Winkel ab"),
+        );
+    }
+
+    /// Prüft die Korrektur des Sensors auf plausible Nullpunktfehler und Skalierungen.
+    /// Weicht eine Achse stark ab, ist vermutlich eine Messung fehlerhaft oder der Sensor defekt.
+    pub fn check_sensor_calibration(&mut self, calibration: &SensorCalibration) {
+        let offset = calibration.offset.abs().max_element();
+        self.push(
+            CheckStatus::rate(offset, SENSOR_OFFSET_LIMITS),
+            "Nullpunkt",
+            format!("größter Nullpunktfehler {offset:.2} m/s²"),
+        );
+
+        let deviation = (calibration.scale - Vec3::ONE).abs().max_element();
+        self.push(
+            CheckStatus::rate(deviation, SENSOR_SCALE_LIMITS),
+            "Skalierung",
+            format!("größte Abweichung der Skalierung von 1: {:.0} %", deviation * 100.0),
+        );
+    }
+
+    /// Gibt die schlechteste Bewertung aller Prüfungen zurück.
+    pub fn worst(&self) -> CheckStatus {
+        self.0.iter().map(|check| check.status).max().unwrap_or(CheckStatus::Pass)
+    }
+
+    /// Gibt alle Prüfungen zeilenweise aus.
+    pub fn print(&self) {
+        println!("Prüfung der Kalibrierung:");
+        for check in &self.0 {
+            println!("  {:<7} {}: {}", check.status, check.name, check.detail);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use glam::{EulerRot, vec3};
+
+    use super::*;
+
+    /// Lagen eines Bildschirms, dessen Halterung um `tilt_degrees` nach hinten geneigt ist,
+    /// gemessen mit einem verdreht eingebauten Sensor mit Nullpunktfehler `bias`.
+    /// Gibt die vier aufrechten Lagen, die flach liegenden Lagen und die Drehung des Sensors zurück.
+    fn tilted_poses(tilt_degrees: f32, bias: Vec3) -> (BTreeMap<Rotation, Vec3>, (Vec3, Vec3), Mat3) {
+        let sensor = Mat3::from_euler(EulerRot::ZYX, 0.4, -0.7, 1.1);
+        let (sin, cos) = tilt_degrees.to_radians().sin_cos();
+        let (c, s) = (cos * STANDARD_GRAVITY, sin * STANDARD_GRAVITY);
+        let measure = |screen: Vec3| sensor * screen + bias;
+
+        let rotations = BTreeMap::from([
+            (Rotation::None, measure(vec3(0.0, -c, -s))),
+            (Rotation::Left, measure(vec3(-c, 0.0, -s))),
+            (Rotation::Inverted, measure(vec3(0.0, c, -s))),
+            (Rotation::Right, measure(vec3(c, 0.0, -s))),
+        ]);
+        let flat = (measure(Vec3::NEG_Z * STANDARD_GRAVITY), measure(Vec3::Z * STANDARD_GRAVITY));
+
+        (rotations, flat, sensor)
+    }
+
+    #[test]
+    fn fit_recovers_tilted_frame() {
+        let bias = vec3(0.3, -0.2, 0.5);
+
+        for flat in [false, true] {
+            let (rotations, flat_poses, sensor) = tilted_poses(30.0, bias);
+            let fit = fit_orientation_vectors(&rotations, flat.then_some(flat_poses)).unwrap();
+            let vectors = &fit.vectors;
+
+            let expected_offset = sensor * vec3(0.0, 0.0, -0.5 * STANDARD_GRAVITY) + bias;
+            assert!(fit.offset.abs_diff_eq(expected_offset, 1e-3), "flat: {flat}");
+            assert!((fit.gravity - 30f32.to_radians().cos() * STANDARD_GRAVITY).abs() < 1e-3);
+            assert!(fit.residual_rms < 1e-3 && fit.residual_max_angle < 1e-3);
+            assert!(vectors.normal.unwrap().cross(sensor * Vec3::Z).length() < 1e-3);
+
+            // Alle aufrechten Lagen liegen in der Ebene, auch `inverted`.
+            for (&rotation, &measurement) in &rotations {
+                assert!(vectors.tilt_out_of_plane(measurement) < 1e-3);
+                let (nearest, angle) = vectors.nearest(measurement);
+                assert!(nearest == rotation && angle < 1e-3);
+            }
+
+            // Flach liegend steht der Messwert senkrecht auf der Ebene, obwohl er durch den Versatz nur halb so weit entfernt ist.
+            assert!(vectors.tilt_out_of_plane(flat_poses.0).to_degrees() > 89.0);
+            assert!(vectors.tilt_out_of_plane(flat_poses.1).to_degrees() > 89.0);
+        }
+    }
+
+    #[test]
+    fn pose_check_accepts_tilted_mount() {
+        let (rotations, flat, _) = tilted_poses(30.0, vec3(0.3, -0.2, 0.5));
+
+        let mut report = QualityReport::default();
+        report.check_measurements(&rotations, Some(flat));
+        assert!(report.worst() == CheckStatus::Pass);
+
+        // Ohne Drehung zwischen zwei Lagen schlägt die Prüfung weiterhin fehl.
+        let mut unrotated = rotations.clone();
+        unrotated.insert(Rotation::Left, rotations[&Rotation::None]);
+        let mut report = QualityReport::default();
+        report.check_measurements(&unrotated, Some(flat));
+        assert!(report.worst() == CheckStatus::Fail);
+    }
+
+    #[test]
+    fn fit_offset_ignores_flat_poses() {
+        // Nullpunktfehler, aber senkrechte Halterung: Versatz und Mittelpunkt der flachen Lagen stimmen überein.
+        let bias = vec3(-0.4, 0.1, 0.2);
+        let (rotations, flat, _) = tilted_poses(0.0, bias);
+
+        let fit = fit_orientation_vectors(&rotations, Some(flat)).unwrap();
+        assert!(fit.offset.abs_diff_eq(bias, 1e-3));
+
+        // Geneigt: Der Versatz stammt nur aus den aufrechten Lagen und wird nicht auf 2/3 verdünnt.
+        let (rotations, flat, sensor) = tilted_poses(45.0, Vec3::ZERO);
+        let expected = sensor * vec3(0.0, 0.0, -45f32.to_radians().sin() * STANDARD_GRAVITY);
+        assert!(fit_orientation_vectors(&rotations, Some(flat)).unwrap().offset.abs_diff_eq(expected, 1e-3));
+    }
+
+    #[test]
+    fn fit_rejects_unrotated_or_missing_poses() {
+        let down = vec3(0.0, -9.8, 0.0);
+        let same = BTreeMap::from([(Rotation::None, down), (Rotation::Left, down), (Rotation::Inverted, -down), (Rotation::Right, -down)]);
+        assert!(fit_orientation_vectors(&same, None).is_err());
+
+        let (mut rotations, _, _) = tilted_poses(10.0, Vec3::ZERO);
+        rotations.remove(&Rotation::Right);
+        assert!(fit_orientation_vectors(&rotations, None).is_err());
+    }
+
+    /// Rohwerte der sechs achsparallelen Lagen eines Sensors mit Empfindlichkeitsmatrix `sensitivity` und Nullpunkt `offset`.
+    fn axis_poses(sensitivity: Mat3, offset: Vec3) -> [[Vec3; 2]; 3] {
+        [Vec3::X, Vec3::Y, Vec3::Z].map(|axis| {
+            let raw = sensitivity * axis * STANDARD_GRAVITY;
+            [offset + raw, offset - raw]
+        })
+    }
+
+    #[test]
+    fn sensor_calibration_recovers_offset_and_scale() {
+        let offset = vec3(0.5, -0.3, 0.8);
+        let poses = axis_poses(Mat3::from_diagonal(vec3(1.1, 0.9, 1.05)), offset);
+
+        let calibration = SensorCalibration::from_axis_poses(&poses, false).unwrap();
+        assert!(calibration.offset.abs_diff_eq(offset, 1e-4));
+        assert!(calibration.scale.abs_diff_eq(vec3(1.0 / 1.1, 1.0 / 0.9, 1.0 / 1.05), 1e-4));
+        assert!(calibration.cross_axis.is_none());
+
+        for (axis, [up, down]) in [Vec3::X, Vec3::Y, Vec3::Z].iter().zip(poses) {
+            assert!(calibration.apply(up).abs_diff_eq(*axis * STANDARD_GRAVITY, 1e-3));
+            assert!(calibration.apply(down).abs_diff_eq(-*axis * STANDARD_GRAVITY, 1e-3));
+        }
+    }
+
+    #[test]
+    fn sensor_calibration_compensates_cross_axis_sensitivity() {
+        let sensitivity = Mat3::from_cols(vec3(1.05, 0.04, -0.03), vec3(0.06, 0.95, 0.02), vec3(-0.02, 0.05, 1.1));
+        let offset = vec3(-0.2, 0.4, 0.1);
+        let poses = axis_poses(sensitivity, offset);
+
+        // Nur mit Ausgleich der Querempfindlichkeit werden auch schräge Lagen korrekt abgebildet.
+        let direction = vec3(0.6, -0.48, 0.64) * STANDARD_GRAVITY;
+        let raw = sensitivity * direction + offset;
+
+        let calibration = SensorCalibration::from_axis_poses(&poses, true).unwrap();
+        assert!(calibration.apply(raw).abs_diff_eq(direction, 1e-3));
+
+        let scaled_only = SensorCalibration::from_axis_poses(&poses, false).unwrap();
+        assert!(!scaled_only.apply(raw).abs_diff_eq(direction, 0.1));
+    }
+
+    #[test]
+    fn sensor_calibration_rejects_wrong_poses() {
+        let poses = axis_poses(Mat3::IDENTITY, Vec3::ZERO);
+
+        let mut swapped_axes = poses;
+        swapped_axes.swap(0, 1);
+        assert!(SensorCalibration::from_axis_poses(&swapped_axes, false).is_err());
+
+        let mut swapped_directions = poses;
+        swapped_directions[2].reverse();
+        assert!(SensorCalibration::from_axis_poses(&swapped_directions, false).is_err());
+
+        let mut invalid = poses;
+        invalid[1][0].y = f32::NAN;
+        assert!(SensorCalibration::from_axis_poses(&invalid, true).is_err());
+    }
+
+    #[test]
+    fn nearest_orthogonal_matrix() {
+        // Eine Drehung ist bereits orthogonal und bleibt unverändert.
+        let rotation = Mat3::from_euler(EulerRot::XYZ, 0.3, 1.2, -0.5);
+        assert!(nearest_orthogonal(rotation).unwrap().abs_diff_eq(rotation, 1e-5));
+
+        // Gescherte und ungleich skalierte Achsen werden zu einem orthonormalen Dreibein.
+        let skewed = Mat3::from_cols(vec3(9.0, 0.0, 0.0), vec3(1.5, 10.5, 0.0), vec3(0.5, -0.5, 8.0));
+        let q = nearest_orthogonal(skewed).unwrap();
+        assert!((q.transpose() * q).abs_diff_eq(Mat3::IDENTITY, 1e-5));
+        assert!((q.determinant() - 1.0).abs() < 1e-5);
+
+        // Symmetrisch verteilte Scherung: Beide Achsen weichen gleich weit von ihrer Messung ab.
+        let sheared = Mat3::from_cols(vec3(1.0, 0.2, 0.0), vec3(0.2, 1.0, 0.0), Vec3::Z);
+        let q = nearest_orthogonal(sheared).unwrap();
+        assert!((q.col(0).angle_between(sheared.col(0)) - q.col(1).angle_between(sheared.col(1))).abs() < 1e-5);
+
+        let flat = Mat3::from_cols(Vec3::X, Vec3::Y, Vec3::X + Vec3::Y);
+        assert!(nearest_orthogonal(flat).is_err());
+    }
+
+    #[test]
+    fn measurement_stops_when_terminated() {
+        // Der Sender bleibt bestehen, es treffen aber keine Messwerte ein.
+        let (_sender, samples) = mpsc::channel();
+
+        let start = Instant::now();
+        let error = measure_stable_pose(&samples, &SignalFlags::terminated()).err().unwrap();
+
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert!(error.downcast_ref::<io::Error>().is_some_and(|e| e.kind() == ErrorKind::Interrupted));
+    }
+
+    #[test]
+    fn errors_received_before_measurement_are_returned() {
+        let (sender, samples) = mpsc::channel();
+        sender.send(Ok(Vec3::Z)).unwrap();
+        sender.send(Err(anyhow::anyhow!("Sensor getrennt"))).unwrap();
+
+        let error = measure_stable_pose(&samples, &SignalFlags::terminated()).err().unwrap();
+        assert_eq!(error.to_string(), "Sensor getrennt");
+    }
+}
@@ -0,0 +1,277 @@
+//! Ausführen externer Programme mit Zeitlimit, Wiederholungen und aufgefangener Fehlerausgabe.
+//!
+//! Ein hängendes `kscreen-doctor` würde sonst die gesamte Rotationsschleife blockieren,
+//! und ein einzelner vorübergehender Fehler würde das Programm beenden.
+
+use std::{io::{self, Read}, process::{Child, Command, ExitStatus, Stdio}, sync::Mutex, thread::{self, JoinHandle}, time::{Duration, Instant}};
+
+use anyhow::{Error, Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+
+/// Standardwert für die maximale Laufzeit eines Aufrufs in Millisekunden.
+const DEFAULT_TIMEOUT_MS: u64 = 10_000;
+
+/// Standardwert für die Anzahl zusätzlicher Versuche nach einem Fehlschlag.
+const DEFAULT_RETRIES: u32 = 2;
+
+/// Standardwert für die Wartezeit vor dem ersten erneuten Versuch in Millisekunden.
+const DEFAULT_RETRY_DELAY_MS: u64 = 200;
+
+/// Exitstatus der Shell, wenn ein Befehl nicht ausführbar ist (126) oder nicht gefunden wurde (127).
+const SHELL_NOT_EXECUTABLE: [i32; 2] = [126, 127];
+
+
+/// Legt fest, wie mit einer Rotation umgegangen wird, die trotz aller Versuche fehlschlägt.
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FailurePolicy {
+    /// Das Programm wird mit dem Fehler beendet
+    #[default]
+    Fatal,
+
+    /// Der Fehler wird ausgegeben und die Rotation beim nächsten Messwert erneut versucht
+    Log,
+}
+
+/// Einstellungen für die Aufrufe von `kscreen-doctor` bzw. der benutzerdefinierten Befehle.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct CommandSettings {
+    /// Maximale Laufzeit eines Aufrufs in Millisekunden, danach wird er beendet
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u64>,
+
+    /// Anzahl zusätzlicher Versuche nach einem Fehlschlag
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retries: Option<u32>,
+
+    /// Wartezeit vor dem ersten erneuten Versuch in Millisekunden; sie verdoppelt sich mit jedem weiteren Versuch
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_delay_ms: Option<u64>,
+
+    /// Verhalten, wenn eine Rotation endgültig fehlschlägt
+    #[serde(default)]
+    pub on_failure: FailurePolicy,
+}
+
+impl CommandSettings {
+    /// Führt den Befehl aus und wiederholt ihn bei einem vorübergehenden Fehler mit wachsender Wartezeit.
+    /// Kann das Programm nicht gefunden oder nicht ausgeführt werden, wird es nicht erneut versucht.
+    ///
+    /// Ist `capture_stdout` gesetzt, wird die Standardausgabe zurückgegeben, ansonsten direkt weitergeleitet.
+    /// Die Fehlerausgabe wird aufgefangen und ist im Fehler enthalten.
+    /// `program` ist der Name des Programms für Fehlermeldungen.
+    pub fn run(&self, command: &mut Command, program: &str, capture_stdout: bool) -> Result<Vec<u8>> {
+        self.run_with_lock(None, command, program, capture_stdout)
+    }
+
+    /// Wie [`run`](Self::run), hält aber während jedes Versuchs `lock`,
+    /// damit sich Aufrufe aus mehreren Threads nicht überschneiden.
+    /// Zwischen den Versuchen wird die Sperre freigegeben, damit andere Threads nicht auf die Wartezeit warten müssen.
+    pub fn run_exclusive(&self, lock: &Mutex<()>, command: &mut Command, program: &str, capture_stdout: bool) -> Result<Vec<u8>> {
+        self.run_with_lock(Some(lock), command, program, capture_stdout)
+    }
+
+    fn run_with_lock(&self, lock: Option<&Mutex<()>>, command: &mut Command, program: &str, capture_stdout: bool) -> Result<Vec<u8>> {
+        let timeout = Duration::from_millis(self.timeout_ms.unwrap_or(DEFAULT_TIMEOUT_MS));
+        let mut delay = Duration::from_millis(self.retry_delay_ms.unwrap_or(DEFAULT_RETRY_DELAY_MS));
+        let mut retries_left = self.retries.unwrap_or(DEFAULT_RETRIES);
+
+        loop {
+            let result = {
+                let _guard = lock.map(|lock| lock.lock().unwrap_or_else(|e| e.into_inner()));
+                run_once(command, program, timeout, capture_stdout)
+            };
+
+            match result {
+                Ok(stdout) => return Ok(stdout),
+                Err(Failure::Transient(e)) if retries_left > 0 => {
+                    eprintln!("{e}; neuer Versuch in {} ms", delay.as_millis());
+                    thread::sleep(delay);
+                    delay *= 2;
+                    retries_left -= 1;
+                }
+                Err(Failure::Transient(e) | Failure::Permanent(e)) => return Err(e),
+            }
+        }
+    }
+}
+
+
+/// Fehler eines einzelnen Aufrufs, eingeteilt danach, ob ein erneuter Versuch sinnvoll ist.
+enum Failure {
+    /// Zeitüberschreitung oder Fehlerstatus, die beim nächsten Versuch verschwinden können
+    Transient(Error),
+
+    /// Das Programm existiert nicht oder darf nicht ausgeführt werden
+    Permanent(Error),
+}
+
+
+/// Führt den Befehl einmal aus und wartet höchstens bis zum Timeout.
+fn run_once(command: &mut Command, program: &str, timeout: Duration, capture_stdout: bool) -> Result<Vec<u8>, Failure> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(if capture_stdout { Stdio::piped() } else { Stdio::inherit() })
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| {
+            let error = anyhow!("{program} konnte nicht gestartet werden: {e}");
+            match e.kind() {
+                io::ErrorKind::NotFound | io::ErrorKind::PermissionDenied => Failure::Permanent(error),
+                _ => Failure::Transient(error),
+            }
+        })?;
+
+    // Die Ausgaben werden in eigenen Threads gelesen, damit ein voller Puffer das Programm nicht blockiert.
+    let stdout = child.stdout.take().map(read_to_end);
+    let stderr = child.stderr.take().map(read_to_end);
+
+    // Nach einer Zeitüberschreitung werden die Lesethreads nicht abgewartet,
+    // da Kindprozesse des beendeten Prozesses die Ausgaben noch offen halten können.
+    let status = wait_with_timeout(&mut child, timeout).map_err(|e| Failure::Transient(anyhow!("{program}: {e}")))?;
+    let stdout = stdout.map(join_output).unwrap_or_default();
+    let stderr = String::from_utf8_lossy(&stderr.map(join_output).unwrap_or_default()).trim().to_string();
+
+    if !status.success() {
+        let error = match stderr.is_empty() {
+            true => anyhow!("{program} wurde mit Status {status} beendet"),
+            false => anyhow!("{program} wurde mit Status {status} beendet: {stderr}"),
+        };
+
+        // Auch eine Shell in einer Befehlsvorlage meldet so, dass sie das Programm nicht ausführen kann.
+        return Err(match status.code() {
+            Some(code) if SHELL_NOT_EXECUTABLE.contains(&code) => Failure::Permanent(error),
+            _ => Failure::Transient(error),
+        });
+    }
+
+    // Warnungen eines erfolgreichen Aufrufs sollen nicht verloren gehen.
+    if !stderr.is_empty() {
+        eprintln!("{stderr}");
+    }
+
+    Ok(stdout)
+}
+
+/// Liest einen Datenstrom in einem Hintergrundthread vollständig ein.
+fn read_to_end(mut reader: impl Read + Send + 'static) -> JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buffer = Vec::new();
+        let _ = reader.read_to_end(&mut buffer);
+        buffer
+    })
+}
+
+/// Wartet auf einen mit [`read_to_end`] gestarteten Thread.
+fn join_output(handle: JoinHandle<Vec<u8>>) -> Vec<u8> {
+    handle.join().unwrap_or_default()
+}
+
+/// Wartet, bis sich der Prozess beendet, und gibt seinen Exitstatus zurück.
+/// Läuft er länger als `timeout`, wird er beendet und ein Fehler zurückgegeben.
+pub fn wait_with_timeout(child: &mut Child, timeout: Duration) -> Result<ExitStatus> {
+    let start = Instant::now();
+
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+
+        if start.elapsed() >= timeout {
+            child.kill()?;
+            child.wait()?;
+            return Err(anyhow!("Zeitüberschreitung nach {} ms", timeout.as_millis()));
+        }
+
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use super::*;
+
+    /// Gibt den Pfad eines leeren Protokolls im temporären Verzeichnis zurück.
+    fn log_file(test_name: &str) -> PathBuf {
+        let log = std::env::temp_dir().join(format!("screen_rotator_command_{test_name}_{}.log", std::process::id()));
+        let _ = fs::remove_file(&log);
+        log
+    }
+
+    fn settings(timeout_ms: u64, retries: u32) -> CommandSettings {
+        CommandSettings { timeout_ms: Some(timeout_ms), retries: Some(retries), retry_delay_ms: Some(1), ..Default::default() }
+    }
+
+    fn shell(script: &str) -> Command {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(script);
+        command
+    }
+
+    #[test]
+    fn hanging_command_is_killed_after_timeout() {
+        let start = Instant::now();
+        let error = settings(100, 0).run(&mut shell("sleep 5"), "stub", true).unwrap_err();
+
+        assert!(error.to_string().contains("Zeitüberschreitung nach 100 ms"), "{error}");
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn transient_failures_are_retried() {
+        let log = log_file("retries");
+        let script = format!("echo attempt >> {}; exit 1", log.display());
+
+        assert!(settings(1000, 2).run(&mut shell(&script), "stub", true).is_err());
+        assert_eq!(fs::read_to_string(&log).unwrap().lines().count(), 3);
+    }
+
+    #[test]
+    fn missing_programs_are_not_retried() {
+        let log = log_file("missing");
+        let script = format!("echo attempt >> {}; exit 127", log.display());
+
+        assert!(settings(1000, 2).run(&mut shell(&script), "stub", true).is_err());
+        assert_eq!(fs::read_to_string(&log).unwrap().lines().count(), 1);
+
+        let start = Instant::now();
+        let error = CommandSettings { retries: Some(2), retry_delay_ms: Some(1000), ..Default::default() }
+            .run(&mut Command::new("/nonexistent/screen_rotator_stub"), "stub", true)
+            .unwrap_err();
+        assert!(error.to_string().contains("konnte nicht gestartet werden"), "{error}");
+        assert!(start.elapsed() < Duration::from_millis(500));
+    }
+
+    #[test]
+    fn error_contains_stderr_and_output_is_returned() {
+        let error = settings(1000, 0).run(&mut shell("echo kaputt >&2; exit 3"), "stub", true).unwrap_err();
+        assert!(error.to_string().contains("beendet: kaputt"), "{error}");
+
+        assert_eq!(settings(1000, 0).run(&mut shell("echo ok"), "stub", true).unwrap(), b"ok\n");
+    }
+
+    #[test]
+    fn lock_is_released_between_attempts() {
+        let lock = Mutex::new(());
+        let log = log_file("lock");
+        let script = format!("echo attempt >> {}; exit 1", log.display());
+        let slow_retries = CommandSettings { retries: Some(1), retry_delay_ms: Some(500), ..Default::default() };
+
+        thread::scope(|s| {
+            s.spawn(|| slow_retries.run_exclusive(&lock, &mut shell(&script), "stub", true));
+
+            // Sobald der erste Versuch protokolliert ist, wartet der andere Thread ohne Sperre auf den nächsten.
+            while !fs::read_to_string(&log).is_ok_and(|log| !log.is_empty()) {
+                thread::sleep(Duration::from_millis(10));
+            }
+
+            let start = Instant::now();
+            settings(1000, 0).run_exclusive(&lock, &mut shell("true"), "stub", true).unwrap();
+            assert!(start.elapsed() < Duration::from_millis(300));
+        });
+    }
+}
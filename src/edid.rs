@@ -0,0 +1,272 @@
+//! Kennung eines Bildschirms aus seiner EDID (Extended Display Identification Data).
+//!
+//! Die EDID wird direkt aus `/sys/class/drm/card*-ANSCHLUSS/edid` gelesen, da die Befehlsvorlagen sie nicht ausgeben.
+//! Gibt `kscreen-doctor -j` eine EDID (Base64-kodiert) mit aus, wird diese verwendet,
+//! wenn sich der Anschluss dort nicht finden lässt, z.B. weil X11 ihn `HDMI-1` statt `HDMI-A-1` nennt.
+
+use std::{fmt::Display, fs, path::Path};
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+
+/// Verzeichnis, in dem der Kernel die Anschlüsse aller Grafikkarten bereitstellt.
+const DRM_DIR: &str = "/sys/class/drm";
+
+/// Feste Kopfzeile am Anfang jedes EDID-Basisblocks.
+const HEADER: [u8; 8] = [0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00];
+
+/// Länge des EDID-Basisblocks in Bytes
+const BLOCK_LENGTH: usize = 128;
+
+/// Positionen der vier 18 Byte langen Deskriptoren im Basisblock
+const DESCRIPTOR_OFFSETS: [usize; 4] = [54, 72, 90, 108];
+
+/// Deskriptor mit dem Modellnamen als Text
+const TAG_NAME: u8 = 0xFC;
+
+/// Deskriptor mit der Seriennummer als Text
+const TAG_SERIAL: u8 = 0xFF;
+
+
+/// Hersteller, Modell und Seriennummer eines Bildschirms aus dessen EDID.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct EdidIdentity {
+    /// Dreibuchstabige PNP-Kennung des Herstellers, z.B. `DEL`
+    #[serde(default)]
+    pub vendor: String,
+
+    /// Modellname laut Deskriptor, ansonsten der Produktcode in hexadezimaler Schreibweise
+    #[serde(default)]
+    pub model: String,
+
+    /// Seriennummer laut Deskriptor, ansonsten die numerische Seriennummer (leer, wenn diese 0 ist)
+    #[serde(default)]
+    pub serial: String,
+}
+
+impl EdidIdentity {
+    /// Liest die EDID des Bildschirms am angegebenen Anschluss, z.B. `DP-1`.
+    /// Gibt [`None`] zurück, wenn der Anschluss nicht existiert, nichts angeschlossen ist
+    /// oder die EDID nicht gelesen werden kann.
+    pub fn read(connector: &str) -> Option<Self> {
+        Self::read_in(Path::new(DRM_DIR), connector)
+    }
+
+    /// Wie [`read`](Self::read), aber im angegebenen Verzeichnis statt in `/sys/class/drm`.
+    fn read_in(drm_dir: &Path, connector: &str) -> Option<Self> {
+        // Die Einträge heißen `card0-DP-1`; die Nummer der Grafikkarte ist nicht bekannt.
+        let entry = fs::read_dir(drm_dir).ok()?.flatten().find(|entry| {
+            entry.file_name().to_str()
+                .and_then(|name| name.strip_prefix("card"))
+                .and_then(|name| name.split_once('-'))
+                .is_some_and(|(card, name)| !card.is_empty() && card.chars().all(|c| c.is_ascii_digit()) && name == connector)
+        })?;
+
+        Self::parse(&fs::read(entry.path().join("edid")).ok()?)
+    }
+
+    /// Liest die Kennung aus dem Basisblock einer EDID.
+    /// Gibt [`None`] zurück, wenn die Daten zu kurz sind oder die Kopfzeile fehlt.
+    pub fn parse(edid: &[u8]) -> Option<Self> {
+        if edid.len() < BLOCK_LENGTH || edid[..8] != HEADER {
+            return None;
+        }
+
+        // Drei Buchstaben zu je 5 Bit, 1 steht für 'A'.
+        let vendor_id = u16::from_be_bytes([edid[8], edid[9]]);
+        let vendor = [10, 5, 0].iter().map(|shift| (b'@' + ((vendor_id >> shift) & 0x1F) as u8) as char).collect();
+
+        let product_code = u16::from_le_bytes([edid[10], edid[11]]);
+        let serial_number = u32::from_le_bytes([edid[12], edid[13], edid[14], edid[15]]);
+
+        let descriptor = |tag: u8| DESCRIPTOR_OFFSETS.iter().find_map(|&offset| {
+            let descriptor = &edid[offset..offset + 18];
+
+            // Anzeigedeskriptoren beginnen mit drei Nullbytes, gefolgt von ihrem Typ.
+            // Der Text endet mit einem Zeilenumbruch und ist mit Leerzeichen aufgefüllt.
+            (descriptor[..3] == [0, 0, 0] && descriptor[3] == tag).then(|| {
+                let text = &descriptor[5..];
+                let end = text.iter().position(|&b| b == b'\n').unwrap_or(text.len());
+                String::from_utf8_lossy(&text[..end]).trim().to_string()
+            })
+        });
+
+        Some(Self {
+            vendor,
+            model: descriptor(TAG_NAME).unwrap_or_else(|| format!("{product_code:04X}")),
+            serial: descriptor(TAG_SERIAL)
+                .unwrap_or_else(|| if serial_number == 0 { String::new() } else { serial_number.to_string() }),
+        })
+    }
+
+    /// Liest die Kennung aus einer Base64-kodierten EDID, wie sie `kscreen-doctor -j` ausgibt.
+    pub fn from_base64(text: &str) -> Option<Self> {
+        Self::parse(&decode_base64(text)?)
+    }
+
+    /// Gibt an, ob die Kennung leer ist, z.B. bei virtuellen Bildschirmen ohne EDID.
+    pub fn is_empty(&self) -> bool {
+        self.vendor.is_empty() && self.model.is_empty() && self.serial.is_empty()
+    }
+}
+
+/// Deserialisiert die Kennung aus der Konfigurationsdatei oder die Base64-kodierte EDID aus `kscreen-doctor -j`.
+/// Eine EDID, die sich nicht lesen lässt, ergibt [`None`] statt eines Fehlers.
+pub fn deserialize_config_or_kscreen<'d, D: Deserializer<'d>>(deserializer: D) -> Result<Option<EdidIdentity>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Edid {
+        Config(EdidIdentity),
+        KScreen(String),
+    }
+
+    Ok(match Option::<Edid>::deserialize(deserializer)? {
+        Some(Edid::Config(identity)) => Some(identity),
+        Some(Edid::KScreen(text)) => EdidIdentity::from_base64(&text),
+        None => None,
+    })
+}
+
+/// Dekodiert Base64 mit dem Standardalphabet; die Auffüllung mit `=` ist optional.
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(text.len() * 3 / 4);
+    let (mut buffer, mut bits) = (0u32, 0);
+
+    for c in text.trim_end_matches('=').bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+
+        buffer = (buffer << 6) | u32::from(value);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+
+    Some(bytes)
+}
+
+/// Ermöglicht die Nutzung von [`Display`] im [`format!`]-Macro.
+impl Display for EdidIdentity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} (Seriennummer {})", self.vendor, self.model, self.serial)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Erzeugt einen Basisblock mit Hersteller `DEL`, Produktcode 0xA0C1 und Seriennummer 12345
+    /// sowie optional einem Namens- und einem Seriennummerndeskriptor.
+    fn edid(name: Option<&str>, serial: Option<&str>) -> Vec<u8> {
+        let mut edid = vec![0; BLOCK_LENGTH];
+        edid[..8].copy_from_slice(&HEADER);
+        edid[8..10].copy_from_slice(&[0x10, 0xAC]);
+        edid[10..12].copy_from_slice(&0xA0C1u16.to_le_bytes());
+        edid[12..16].copy_from_slice(&12345u32.to_le_bytes());
+
+        for (offset, tag, text) in [(72, TAG_SERIAL, serial), (90, TAG_NAME, name)] {
+            let Some(text) = text else { continue };
+            let descriptor = &mut edid[offset..offset + 18];
+            descriptor[3] = tag;
+            descriptor[5..].fill(b' ');
+            descriptor[5..5 + text.len()].copy_from_slice(text.as_bytes());
+            descriptor[5 + text.len()] = b'\n';
+        }
+
+        edid
+    }
+
+    #[test]
+    fn parses_descriptors() {
+        let identity = EdidIdentity::parse(&edid(Some("DELL U2720Q"), Some("ABC123"))).unwrap();
+
+        assert_eq!(identity.vendor, "DEL");
+        assert_eq!(identity.model, "DELL U2720Q");
+        assert_eq!(identity.serial, "ABC123");
+    }
+
+    #[test]
+    fn falls_back_to_numeric_fields() {
+        let identity = EdidIdentity::parse(&edid(None, None)).unwrap();
+
+        assert_eq!(identity.model, "A0C1");
+        assert_eq!(identity.serial, "12345");
+    }
+
+    #[test]
+    fn rejects_invalid_data() {
+        assert!(EdidIdentity::parse(&[]).is_none());
+        assert!(EdidIdentity::parse(&edid(None, None)[..100]).is_none());
+        assert!(EdidIdentity::parse(&[0; BLOCK_LENGTH]).is_none());
+    }
+
+    /// Kodiert Daten als Base64 mit Auffüllung.
+    fn encode_base64(data: &[u8]) -> String {
+        const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+        data.chunks(3).flat_map(|chunk| {
+            let value = chunk.iter().enumerate().fold(0u32, |value, (i, &b)| value | u32::from(b) << (16 - 8 * i));
+            (0..4).map(move |i| match i <= chunk.len() {
+                true => ALPHABET[(value >> (18 - 6 * i) & 0x3F) as usize] as char,
+                false => '=',
+            })
+        }).collect()
+    }
+
+    #[test]
+    fn decodes_base64() {
+        assert_eq!(decode_base64("TWFueQ==").unwrap(), b"Many");
+        assert_eq!(decode_base64("TWFu").unwrap(), b"Man");
+        assert_eq!(decode_base64("TWE").unwrap(), b"Ma");
+        assert!(decode_base64("TW-u").is_none());
+
+        let data = edid(Some("DELL U2720Q"), None);
+        assert_eq!(decode_base64(&encode_base64(&data)).unwrap(), data);
+    }
+
+    #[test]
+    fn deserializes_config_and_kscreen_format() {
+        #[derive(Deserialize)]
+        struct Output {
+            #[serde(default, deserialize_with = "deserialize_config_or_kscreen")]
+            edid: Option<EdidIdentity>,
+        }
+
+        let parse = |json: String| serde_json::from_str::<Output>(&json).unwrap().edid;
+
+        let config = parse(r#"{"edid": {"vendor": "DEL", "model": "DELL U2720Q", "serial": "ABC123"}}"#.to_string()).unwrap();
+        assert_eq!(config.serial, "ABC123");
+
+        let kscreen = parse(format!(r#"{{"edid": "{}"}}"#, encode_base64(&edid(Some("DELL U2720Q"), Some("ABC123"))))).unwrap();
+        assert!(kscreen == config);
+
+        assert!(parse(r#"{"edid": "kein EDID"}"#.to_string()).is_none());
+        assert!(parse(r#"{"edid": null}"#.to_string()).is_none());
+        assert!(parse("{}".to_string()).is_none());
+    }
+
+    #[test]
+    fn reads_connector_from_any_card() {
+        let dir = std::env::temp_dir().join(format!("screen_rotator_drm_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        for (entry, data) in [("card1-DP-1", edid(Some("A"), None)), ("card1-DP-10", edid(Some("B"), None)), ("card1-HDMI-A-1", vec![])] {
+            fs::create_dir_all(dir.join(entry)).unwrap();
+            fs::write(dir.join(entry).join("edid"), data).unwrap();
+        }
+
+        assert!(EdidIdentity::read_in(&dir, "DP-1").is_some_and(|identity| identity.model == "A"));
+        assert!(EdidIdentity::read_in(&dir, "HDMI-A-1").is_none());
+        assert!(EdidIdentity::read_in(&dir, "DP-2").is_none());
+    }
+}
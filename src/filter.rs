@@ -0,0 +1,319 @@
+//! Entscheidet anhand der gemessenen Beschleunigung, wann der Bildschirm rotiert werden soll.
+//!
+//! Würde bei jedem Messwert sofort die nächstgelegene Ausrichtung übernommen,
+//! könnte der Bildschirm in der Nähe von 45° ständig zwischen zwei Ausrichtungen hin- und herspringen.
+//! Deshalb muss eine neue Ausrichtung um einen Mindestwinkel (Hysterese) besser passen
+//! und diesen Vorsprung für eine Mindestdauer (Verweildauer) halten.
+//!
+//! Liegt der Bildschirm flach, ist die Ausrichtung nicht bestimmbar; dann wird die aktuelle beibehalten.
+//! Dasselbe gilt, solange der Bildschirm bewegt wird, da dann die Beschleunigung durch die Bewegung überwiegt.
+
+use std::{collections::VecDeque, time::{Duration, Instant}};
+
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+
+use crate::monitor::{OrientationVectors, Rotation};
+
+
+/// Standardwert für die Hysterese in Grad.
+pub const DEFAULT_HYSTERESIS_DEGREES: f32 = 10.0;
+
+/// Standardwert für die Verweildauer in Millisekunden.
+pub const DEFAULT_DWELL_TIME_MS: u64 = 500;
+
+/// Standardwert für die Neigung aus der Rotationsebene in Grad, ab der der Bildschirm als flach liegend gilt.
+pub const DEFAULT_FLAT_THRESHOLD_DEGREES: f32 = 60.0;
+
+
+/// Gibt an, ob der Bildschirm so flach liegt, dass keine Ausrichtung zuverlässig bestimmt werden kann.
+///
+/// Das ist der Fall, wenn die Schwerkraft um mehr als `threshold_degrees` aus der Rotationsebene
+/// (aufgespannt durch die Richtungsvektoren) heraus geneigt ist.
+pub fn is_lying_flat(acc: Vec3, orientations: &OrientationVectors, threshold_degrees: f32) -> bool {
+    orientations.tilt_out_of_plane(acc) > threshold_degrees.to_radians()
+}
+
+
+/// Entprellt die Wahl der Ausrichtung durch Hysterese und Verweildauer.
+pub struct RotationDebouncer {
+    /// Winkel im Bogenmaß, um den die neue Ausrichtung näher am Messwert liegen muss als die aktuelle.
+    hysteresis: f32,
+
+    /// Zeit, die eine neue Ausrichtung ununterbrochen gewinnen muss, bevor sie übernommen wird.
+    dwell_time: Duration,
+
+    /// Die Ausrichtung, die aktuell gewinnt, aber noch nicht übernommen wurde, und seit wann sie gewinnt.
+    candidate: Option<(Rotation, Instant)>,
+}
+
+impl RotationDebouncer {
+    /// Erstellt einen neuen [`RotationDebouncer`].
+    /// `hysteresis_degrees` wird in Grad angegeben.
+    pub fn new(hysteresis_degrees: f32, dwell_time: Duration) -> Self {
+        Self {
+            hysteresis: hysteresis_degrees.to_radians(),
+            dwell_time,
+            candidate: None,
+        }
+    }
+
+    /// Verarbeitet einen Messwert und gibt die Ausrichtung zurück, zu der rotiert werden soll.
+    /// Gibt [`None`] zurück, solange die aktuelle Ausrichtung beibehalten werden soll.
+    ///
+    /// Ist die aktuelle Ausrichtung unbekannt, entfällt die Hysterese.
+    pub fn update(&mut self, acc: Vec3, orientations: &OrientationVectors, current: Option<Rotation>, now: Instant) -> Option<Rotation> {
+        let (winner, winner_angle) = orientations.nearest(acc);
+
+        // Abstand des Messwerts zur aktuellen Ausrichtung; ohne bekannte Ausrichtung gewinnt jede andere.
+        let current_angle = current.map_or(f32::INFINITY, |r| orientations.angle_to(r, acc));
+
+        if Some(winner) == current || current_angle - winner_angle < self.hysteresis {
+            self.candidate = None;
+            return None;
+        }
+
+        // Die Verweildauer beginnt erst, wenn diese Ausrichtung zum ersten Mal in Folge gewinnt.
+        let since = match self.candidate {
+            Some((candidate, since)) if candidate == winner => since,
+            _ => now,
+        };
+
+        if now.duration_since(since) >= self.dwell_time {
+            self.candidate = None;
+            Some(winner)
+        } else {
+            self.candidate = Some((winner, since));
+            None
+        }
+    }
+
+    /// Verwirft die noch nicht übernommene Ausrichtung, z.B. wenn Messwerte übersprungen wurden.
+    pub fn reset(&mut self) {
+        self.candidate = None;
+    }
+}
+
+
+/// Einstellungen für den [`MotionDetector`].
+/// Fehlende Felder in der Konfigurationsdatei erhalten ihren Standardwert.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct MotionSettings {
+    /// Zeitfenster in Millisekunden, über das die Schwankung der Messwerte berechnet wird
+    pub window_ms: u64,
+
+    /// Maximale Standardabweichung des Betrags der Beschleunigung (in m/s²), bei der das Gerät als ruhig gilt
+    pub magnitude_threshold: f32,
+
+    /// Maximale Abweichung der Richtung vom Mittelwert in Grad, bei der das Gerät als ruhig gilt
+    pub direction_threshold_degrees: f32,
+
+    /// Zeit in Millisekunden, die das Gerät ununterbrochen ruhig sein muss, bevor es als ruhend gilt
+    pub rest_time_ms: u64,
+}
+
+impl Default for MotionSettings {
+    fn default() -> Self {
+        Self {
+            window_ms: 500,
+            magnitude_threshold: 0.5,
+            direction_threshold_degrees: 5.0,
+            rest_time_ms: 300,
+        }
+    }
+}
+
+/// Erkennt anhand der kurzfristigen Schwankung von Betrag und Richtung der Beschleunigung,
+/// ob das Gerät gerade bewegt wird oder ruht.
+pub struct MotionDetector {
+    settings: MotionSettings,
+
+    /// Messwerte innerhalb des Zeitfensters, zusammen mit ihrem Zeitpunkt
+    samples: VecDeque<(Instant, Vec3)>,
+
+    /// Zeitpunkt, seit dem die Messwerte ununterbrochen ruhig sind
+    quiet_since: Option<Instant>,
+
+    /// Ergebnis der letzten Auswertung
+    resting: bool,
+}
+
+impl MotionDetector {
+    /// Erstellt einen neuen [`MotionDetector`]. Zu Beginn gilt das Gerät als nicht ruhend.
+    pub fn new(settings: MotionSettings) -> Self {
+        Self {
+            settings,
+            samples: VecDeque::new(),
+            quiet_since: None,
+            resting: false,
+        }
+    }
+
+    /// Fügt einen Messwert hinzu und aktualisiert den Ruhezustand.
+    pub fn update(&mut self, acc: Vec3, now: Instant) {
+        self.samples.push_back((now, acc));
+
+        // Entferne alle Messwerte, die älter als das Zeitfenster sind.
+        let window = Duration::from_millis(self.settings.window_ms);
+        while let Some(&(time, _)) = self.samples.front() && now.duration_since(time) > window {
+            self.samples.pop_front();
+        }
+
+        if self.is_quiet() {
+            let since = *self.quiet_since.get_or_insert(now);
+            self.resting = now.duration_since(since) >= Duration::from_millis(self.settings.rest_time_ms);
+        } else {
+            self.quiet_since = None;
+            self.resting = false;
+        }
+    }
+
+    /// Gibt an, ob das Gerät seit mindestens der eingestellten Ruhezeit nicht bewegt wurde.
+    pub fn is_resting(&self) -> bool {
+        self.resting
+    }
+
+    /// Gibt an, ob die Messwerte im Zeitfenster nur gering schwanken.
+    /// Mit weniger als zwei Messwerten ist keine Aussage möglich.
+    fn is_quiet(&self) -> bool {
+        if self.samples.len() < 2 {
+            return false;
+        }
+
+        let count = self.samples.len() as f32;
+
+        // Standardabweichung des Betrags
+        let mean_length = self.samples.iter().map(|(_, acc)| acc.length()).sum::<f32>() / count;
+        let variance = self.samples
+            .iter()
+            .map(|(_, acc)| (acc.length() - mean_length).powi(2))
+            .sum::<f32>() / count;
+
+        // größte Abweichung der Richtung von der mittleren Richtung
+        let mean_direction = self.samples.iter().map(|(_, acc)| acc.normalize_or_zero()).sum::<Vec3>();
+        let max_deviation = self.samples
+            .iter()
+            .map(|(_, acc)| acc.angle_between(mean_direction))
+            .fold(0.0, f32::max);
+
+        variance.sqrt() <= self.settings.magnitude_threshold
+            && max_deviation <= self.settings.direction_threshold_degrees.to_radians()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use glam::vec3;
+
+    use super::*;
+
+    fn orientations() -> OrientationVectors {
+        OrientationVectors::centered(vec3(0.0, -9.8, 0.0), vec3(-9.8, 0.0, 0.0), Vec3::ZERO, None)
+    }
+
+    /// Messwert, der um `degrees` von `none` in Richtung `left` gedreht ist.
+    fn tilted_towards_left(degrees: f32) -> Vec3 {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        vec3(-sin, -cos, 0.0) * 9.8
+    }
+
+    #[test]
+    fn hysteresis_keeps_current_rotation_near_the_boundary() {
+        let mut debouncer = RotationDebouncer::new(10.0, Duration::ZERO);
+        let now = Instant::now();
+
+        // `left` gewinnt mit 42° gegenüber 48°, aber nicht um die geforderten 10°.
+        assert!(debouncer.update(tilted_towards_left(48.0), &orientations(), Some(Rotation::None), now).is_none());
+        assert!(debouncer.update(tilted_towards_left(60.0), &orientations(), Some(Rotation::None), now) == Some(Rotation::Left));
+    }
+
+    #[test]
+    fn unknown_rotation_skips_hysteresis() {
+        let mut debouncer = RotationDebouncer::new(10.0, Duration::ZERO);
+
+        assert!(debouncer.update(tilted_towards_left(48.0), &orientations(), None, Instant::now()) == Some(Rotation::Left));
+    }
+
+    #[test]
+    fn new_rotation_must_win_for_the_dwell_time() {
+        let mut debouncer = RotationDebouncer::new(10.0, Duration::from_millis(500));
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let left = tilted_towards_left(80.0);
+        let none = tilted_towards_left(5.0);
+
+        assert!(debouncer.update(left, &orientations(), Some(Rotation::None), at(0)).is_none());
+        assert!(debouncer.update(left, &orientations(), Some(Rotation::None), at(400)).is_none());
+
+        // Eine Unterbrechung startet die Verweildauer neu.
+        assert!(debouncer.update(none, &orientations(), Some(Rotation::None), at(450)).is_none());
+        assert!(debouncer.update(left, &orientations(), Some(Rotation::None), at(600)).is_none());
+        assert!(debouncer.update(left, &orientations(), Some(Rotation::None), at(1000)).is_none());
+        assert!(debouncer.update(left, &orientations(), Some(Rotation::None), at(1100)) == Some(Rotation::Left));
+    }
+
+    #[test]
+    fn reset_discards_the_candidate() {
+        let mut debouncer = RotationDebouncer::new(10.0, Duration::from_millis(500));
+        let start = Instant::now();
+        let left = tilted_towards_left(80.0);
+
+        assert!(debouncer.update(left, &orientations(), Some(Rotation::None), start).is_none());
+        debouncer.reset();
+        assert!(debouncer.update(left, &orientations(), Some(Rotation::None), start + Duration::from_millis(500)).is_none());
+        assert!(debouncer.update(left, &orientations(), Some(Rotation::None), start + Duration::from_millis(1000)) == Some(Rotation::Left));
+    }
+
+    /// Führt abwechselnd die Messwerte `a` und `b` im Abstand von 100 ms zu, beginnend bei `from_ms`.
+    /// Gibt den Ruhezustand nach dem letzten Messwert zurück.
+    fn feed(detector: &mut MotionDetector, start: Instant, from_ms: u64, to_ms: u64, a: Vec3, b: Vec3) -> bool {
+        for (i, ms) in (from_ms..=to_ms).step_by(100).enumerate() {
+            detector.update(if i % 2 == 0 { a } else { b }, start + Duration::from_millis(ms));
+        }
+        detector.is_resting()
+    }
+
+    #[test]
+    fn motion_detector_settles_after_rest_time() {
+        let mut detector = MotionDetector::new(MotionSettings::default());
+        let start = Instant::now();
+        let still = vec3(0.0, -9.8, 0.0);
+
+        // Ab dem zweiten Messwert (100 ms) ist das Fenster ruhig; nach weiteren 300 ms gilt das Gerät als ruhend.
+        assert!(!feed(&mut detector, start, 0, 300, still, still));
+        assert!(feed(&mut detector, start, 400, 400, still, still));
+    }
+
+    #[test]
+    fn motion_detector_reports_burst_as_moving() {
+        let mut detector = MotionDetector::new(MotionSettings::default());
+        let start = Instant::now();
+        let still = vec3(0.0, -9.8, 0.0);
+        let shaken = vec3(3.0, -12.0, 1.0);
+
+        assert!(feed(&mut detector, start, 0, 1000, still, still));
+        assert!(!feed(&mut detector, start, 1100, 1400, shaken, still));
+
+        // Der Stoß bleibt 500 ms im Zeitfenster, danach beginnt die Ruhezeit von 300 ms.
+        assert!(!feed(&mut detector, start, 1500, 2100, still, still));
+        assert!(feed(&mut detector, start, 2200, 2200, still, still));
+    }
+
+    #[test]
+    fn motion_detector_thresholds() {
+        let settings = MotionSettings { rest_time_ms: 0, ..MotionSettings::default() };
+        let start = Instant::now();
+        let down = vec3(0.0, -1.0, 0.0);
+        let tilted = |degrees: f32| Vec3::new(degrees.to_radians().sin(), -degrees.to_radians().cos(), 0.0) * 9.8;
+
+        // Standardabweichung des Betrags 0,4 bzw. 0,6 m/s² bei einer Schwelle von 0,5 m/s²
+        assert!(feed(&mut MotionDetector::new(settings.clone()), start, 0, 400, down * 9.4, down * 10.2));
+        assert!(!feed(&mut MotionDetector::new(settings.clone()), start, 0, 400, down * 9.2, down * 10.4));
+
+        // Abweichung der Richtung 4° bzw. 6° bei einer Schwelle von 5°
+        assert!(feed(&mut MotionDetector::new(settings.clone()), start, 0, 300, tilted(4.0), tilted(-4.0)));
+        assert!(!feed(&mut MotionDetector::new(settings), start, 0, 300, tilted(6.0), tilted(-6.0)));
+    }
+}
@@ -0,0 +1,181 @@
+//! Ausführen benutzerdefinierter Befehle (Hooks), nachdem der Bildschirm rotiert wurde.
+//!
+//! Damit lassen sich z.B. Widgets neu anordnen oder ein Touchscreen neu zuordnen.
+//! Die Befehle werden über `sh -c` ausgeführt und erhalten folgende Umgebungsvariablen:
+//! - `SCREEN_ROTATOR_OLD_ROTATION`: vorherige Rotation (leer, wenn unbekannt)
+//! - `SCREEN_ROTATOR_NEW_ROTATION`: neue Rotation
+//! - `SCREEN_ROTATOR_MONITOR`: Name des Bildschirms
+//! - `SCREEN_ROTATOR_VECTOR`: gemessener Beschleunigungsvektor als `x,y,z`
+//!
+//! Die Befehle laufen in einem eigenen Thread, nachdem alle Bildschirme der Gruppe rotiert wurden,
+//! damit ein langsamer Hook weder die Rotation noch das Lesen der Messwerte aufhält.
+
+use std::{collections::BTreeMap, process::{Command, Stdio}, sync::{Mutex, PoisonError}, thread::{self, JoinHandle}, time::Duration};
+
+use anyhow::{Result, bail};
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+
+use crate::{command, monitor::Rotation};
+
+
+/// Standardwert für die maximale Laufzeit eines Hooks in Millisekunden.
+const DEFAULT_TIMEOUT_MS: u64 = 5000;
+
+/// Verhindert, dass die Hooks mehrerer kurz aufeinanderfolgender Rotationen gleichzeitig laufen.
+static HOOK_LOCK: Mutex<()> = Mutex::new(());
+
+
+/// Anlass, zu dem die Hooks eines Bildschirms ausgeführt werden.
+pub struct HookEvent {
+    /// Vorherige Rotation des Bildschirms, sofern bekannt
+    pub old: Option<Rotation>,
+    pub new: Rotation,
+    pub monitor_name: String,
+
+    /// Messwert, der zur Rotation geführt hat
+    pub acc: Vec3,
+}
+
+
+/// Befehle, die nach einer Rotation ausgeführt werden.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct HookSettings {
+    /// Befehle, die nur bei einer bestimmten neuen Rotation ausgeführt werden
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub per_rotation: BTreeMap<Rotation, String>,
+
+    /// Befehl, der nach jeder Rotation ausgeführt wird (nach dem rotationsspezifischen Befehl)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_rotate: Option<String>,
+
+    /// Maximale Laufzeit eines Befehls in Millisekunden, danach wird er beendet
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u64>,
+}
+
+impl HookSettings {
+    /// Führt die passenden Befehle aller Ereignisse nacheinander in einem eigenen Thread aus.
+    /// Hooks verschiedener Aufrufe laufen dabei nie gleichzeitig.
+    ///
+    /// Gibt den Thread zurück, sofern Befehle konfiguriert sind.
+    /// Er muss nur abgewartet werden, wenn sich das Programm anschließend beendet.
+    pub fn spawn(&self, events: Vec<HookEvent>) -> Option<JoinHandle<()>> {
+        if self.per_rotation.is_empty() && self.on_rotate.is_none() {
+            return None;
+        }
+
+        let settings = self.clone();
+        Some(thread::spawn(move || {
+            let _guard = HOOK_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+            for event in &events {
+                settings.run(event);
+            }
+        }))
+    }
+
+    /// Führt alle zum Ereignis passenden Befehle nacheinander aus.
+    /// Fehler werden ausgegeben, beenden aber nicht das Programm.
+    fn run(&self, event: &HookEvent) {
+        let commands = self.per_rotation.get(&event.new).into_iter().chain(&self.on_rotate);
+
+        for command in commands {
+            if let Err(e) = self.run_command(command, event) {
+                eprintln!("Hook \"{command}\" fehlgeschlagen: {e}");
+            }
+        }
+    }
+
+    /// Führt einen einzelnen Befehl mit den Umgebungsvariablen aus und wartet höchstens bis zum Timeout.
+    fn run_command(&self, command: &str, event: &HookEvent) -> Result<()> {
+        let acc = event.acc;
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .env("SCREEN_ROTATOR_OLD_ROTATION", event.old.map(|r| r.to_string()).unwrap_or_default())
+            .env("SCREEN_ROTATOR_NEW_ROTATION", event.new.to_string())
+            .env("SCREEN_ROTATOR_MONITOR", &event.monitor_name)
+            .env("SCREEN_ROTATOR_VECTOR", format!("{},{},{}", acc.x, acc.y, acc.z))
+            .stdin(Stdio::null())
+            .stdout(Stdio::inherit())
+            .stderr(Stdio::inherit())
+            .spawn()?;
+
+        let timeout = Duration::from_millis(self.timeout_ms.unwrap_or(DEFAULT_TIMEOUT_MS));
+        let status = command::wait_with_timeout(&mut child, timeout)?;
+
+        if !status.success() {
+            bail!("Befehl wurde mit Status {status} beendet");
+        }
+
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::{Path, PathBuf}, time::Instant};
+
+    use super::*;
+
+    /// Gibt den Pfad eines leeren Protokolls im temporären Verzeichnis zurück.
+    fn log_file(test_name: &str) -> PathBuf {
+        let log = std::env::temp_dir().join(format!("screen_rotator_hooks_{test_name}_{}.log", std::process::id()));
+        let _ = fs::remove_file(&log);
+        log
+    }
+
+    /// Befehl, der `label` und alle Umgebungsvariablen in das Protokoll schreibt.
+    fn recording_hook(label: &str, log: &Path) -> String {
+        format!(
+            "echo \"{label} $SCREEN_ROTATOR_OLD_ROTATION/$SCREEN_ROTATOR_NEW_ROTATION $SCREEN_ROTATOR_MONITOR $SCREEN_ROTATOR_VECTOR\" >> {}",
+            log.display(),
+        )
+    }
+
+    fn event(old: Option<Rotation>, new: Rotation, monitor_name: &str) -> HookEvent {
+        HookEvent { old, new, monitor_name: monitor_name.to_string(), acc: Vec3::new(-9.5, 0.25, 1.0) }
+    }
+
+    #[test]
+    fn selects_commands_and_passes_environment() {
+        let log = log_file("environment");
+        let hooks = HookSettings {
+            per_rotation: BTreeMap::from([(Rotation::Left, recording_hook("left", &log))]),
+            on_rotate: Some(recording_hook("any", &log)),
+            timeout_ms: None,
+        };
+
+        let events = vec![event(Some(Rotation::None), Rotation::Left, "DP-1"), event(None, Rotation::Right, "HDMI-A-1")];
+        hooks.spawn(events).unwrap().join().unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&log).unwrap(),
+            "left none/left DP-1 -9.5,0.25,1\nany none/left DP-1 -9.5,0.25,1\nany /right HDMI-A-1 -9.5,0.25,1\n",
+        );
+    }
+
+    #[test]
+    fn slow_hooks_run_in_background_and_time_out() {
+        let log = log_file("timeout");
+        let hooks = HookSettings {
+            per_rotation: BTreeMap::new(),
+            on_rotate: Some(format!("echo start >> {0}; sleep 5; echo end >> {0}", log.display())),
+            timeout_ms: Some(200),
+        };
+
+        let start = Instant::now();
+        let handle = hooks.spawn(vec![event(None, Rotation::None, "DP-1")]).unwrap();
+        assert!(start.elapsed() < Duration::from_millis(100));
+
+        handle.join().unwrap();
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert_eq!(fs::read_to_string(&log).unwrap(), "start\n");
+    }
+
+    #[test]
+    fn nothing_is_spawned_without_commands() {
+        assert!(HookSettings::default().spawn(vec![event(None, Rotation::None, "DP-1")]).is_none());
+    }
+}
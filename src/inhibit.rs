@@ -0,0 +1,267 @@
+//! Bedingungen, unter denen die automatische Rotation vorübergehend angehalten wird,
+//! z.B. solange ein Videoplayer oder ein Präsentationsprogramm läuft.
+//!
+//! Im Gegensatz zur Rotationssperre muss dafür niemand etwas umschalten:
+//! Die Bedingungen werden regelmäßig geprüft, und die Rotation läuft weiter, sobald keine mehr zutrifft.
+//! Unterstützt werden laufende Prozesse, vorhandene Dateien und Befehle, die mit Status 0 enden.
+//! Die Prüfung läuft in einem eigenen Thread, damit langsame Befehle das Lesen der Messwerte nicht aufhalten.
+
+use std::{fs, path::{Path, PathBuf}, process::{Command, Stdio}, sync::Arc, thread::{self, JoinHandle}, time::{Duration, Instant}};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::command;
+
+
+/// Standardwert für den Abstand zwischen zwei Prüfungen in Millisekunden.
+const DEFAULT_INTERVAL_MS: u64 = 2000;
+
+/// Maximale Laufzeit eines Prüfbefehls in Millisekunden.
+/// Ein hängender Befehl gilt als nicht zutreffend.
+const COMMAND_TIMEOUT_MS: u64 = 1000;
+
+
+/// Bedingungen, die die automatische Rotation anhalten.
+/// Es genügt, wenn eine davon zutrifft.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct InhibitSettings {
+    /// Namen von Prozessen (z.B. `mpv`), während deren Laufzeit nicht rotiert wird
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub processes: Vec<String>,
+
+    /// Dateien, während deren Existenz nicht rotiert wird
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<PathBuf>,
+
+    /// Befehle, die über `sh -c` ausgeführt werden; endet einer mit Status 0, wird nicht rotiert
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub commands: Vec<String>,
+
+    /// Abstand zwischen zwei Prüfungen in Millisekunden
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interval_ms: Option<u64>,
+}
+
+impl InhibitSettings {
+    /// Gibt an, ob keine Bedingung konfiguriert ist.
+    fn is_empty(&self) -> bool {
+        self.processes.is_empty() && self.files.is_empty() && self.commands.is_empty()
+    }
+
+    /// Prüft alle Bedingungen und gibt eine Beschreibung der ersten zutreffenden zurück.
+    /// Fehler beim Prüfen werden ausgegeben; die Bedingung gilt dann als nicht zutreffend.
+    fn check(&self) -> Option<String> {
+        if let Some(path) = self.files.iter().find(|path| path.exists()) {
+            return Some(format!("Datei {} existiert", path.display()));
+        }
+
+        if !self.processes.is_empty() {
+            match running_process(&self.processes) {
+                Ok(Some(name)) => return Some(format!("Prozess {name} läuft")),
+                Ok(None) => {}
+                Err(e) => eprintln!("Prozesse konnten nicht abgefragt werden: {e}"),
+            }
+        }
+
+        for command in &self.commands {
+            match command_succeeds(command) {
+                Ok(true) => return Some(format!("Befehl \"{command}\" trifft zu")),
+                Ok(false) => {}
+                Err(e) => eprintln!("Befehl \"{command}\" konnte nicht geprüft werden: {e}"),
+            }
+        }
+
+        None
+    }
+}
+
+
+/// Merkt sich das Ergebnis der letzten Prüfung, damit nicht bei jedem Messwert geprüft wird.
+pub struct Inhibitor {
+    settings: Arc<InhibitSettings>,
+    interval: Duration,
+    last_check: Option<Instant>,
+    reason: Option<String>,
+
+    /// Prüfung, die gerade im Hintergrund läuft
+    pending: Option<JoinHandle<Option<String>>>,
+}
+
+impl Inhibitor {
+    /// Erstellt einen [`Inhibitor`]; die erste Prüfung beginnt beim ersten Aufruf von [`update`](Self::update).
+    pub fn new(settings: InhibitSettings) -> Self {
+        let interval = Duration::from_millis(settings.interval_ms.unwrap_or(DEFAULT_INTERVAL_MS));
+        Self { settings: Arc::new(settings), interval, last_check: None, reason: None, pending: None }
+    }
+
+    /// Übernimmt das Ergebnis einer abgeschlossenen Prüfung und startet eine neue im Hintergrund,
+    /// sofern seit dem Start der letzten genug Zeit vergangen ist. Wartet nie auf eine laufende Prüfung.
+    /// Gibt `true` zurück, wenn sich dadurch geändert hat, ob die Rotation angehalten ist.
+    pub fn update(&mut self, now: Instant) -> bool {
+        if self.settings.is_empty() {
+            return false;
+        }
+
+        let changed = self.pending.as_ref().is_some_and(JoinHandle::is_finished) && self.finish();
+
+        if self.pending.is_none() && self.last_check.is_none_or(|t| now.duration_since(t) >= self.interval) {
+            self.last_check = Some(now);
+            let settings = Arc::clone(&self.settings);
+            self.pending = Some(thread::spawn(move || settings.check()));
+        }
+
+        changed
+    }
+
+    /// Wartet auf eine laufende Prüfung und übernimmt ihr Ergebnis, z.B. wenn nur ein einziges Mal rotiert wird.
+    /// Gibt `true` zurück, wenn sich dadurch geändert hat, ob die Rotation angehalten ist.
+    pub fn finish(&mut self) -> bool {
+        let Some(pending) = self.pending.take() else { return false };
+
+        // Eine abgebrochene Prüfung gilt wie ein Fehler beim Prüfen als nicht zutreffend.
+        let reason = pending.join().unwrap_or_default();
+        let changed = reason.is_some() != self.reason.is_some();
+        self.reason = reason;
+        changed
+    }
+
+    /// Gibt die Beschreibung der zutreffenden Bedingung zurück, oder [`None`], wenn die Rotation nicht angehalten ist.
+    pub fn reason(&self) -> Option<&str> {
+        self.reason.as_deref()
+    }
+}
+
+
+/// Sucht in `/proc` nach einem laufenden Prozess mit einem der angegebenen Namen und gibt den gefundenen Namen zurück.
+///
+/// Verglichen wird mit dem Prozessnamen (`comm`, vom Kernel auf 15 Zeichen gekürzt)
+/// und mit dem Dateinamen des gestarteten Programms, damit auch lange Namen gefunden werden.
+fn running_process(names: &[String]) -> Result<Option<&str>> {
+    for entry in fs::read_dir("/proc")? {
+        let path = entry?.path();
+
+        // Nur die Verzeichnisse mit numerischem Namen gehören zu Prozessen.
+        if !path.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.bytes().all(|b| b.is_ascii_digit())) {
+            continue;
+        }
+
+        // Der Prozess kann sich inzwischen beendet haben; dann fehlen die Dateien.
+        let comm = fs::read_to_string(path.join("comm")).unwrap_or_default();
+        let cmdline = fs::read(path.join("cmdline")).unwrap_or_default();
+        let program = cmdline
+            .split(|&b| b == 0)
+            .next()
+            .and_then(|arg| Path::new(&*String::from_utf8_lossy(arg)).file_name().map(|n| n.to_string_lossy().into_owned()))
+            .unwrap_or_default();
+
+        if let Some(name) = names.iter().find(|name| comm.trim_end() == name.as_str() || program == **name) {
+            return Ok(Some(name));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Führt einen Befehl aus und gibt an, ob er mit Status 0 endet.
+fn command_succeeds(command: &str) -> Result<bool> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::inherit())
+        .spawn()?;
+
+    let status = command::wait_with_timeout(&mut child, Duration::from_millis(COMMAND_TIMEOUT_MS))?;
+    Ok(status.success())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Gibt einen Pfad im temporären Verzeichnis zurück, unter dem noch keine Datei liegt.
+    fn temp_path(test_name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("screen_rotator_inhibit_{test_name}_{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn with_conditions(files: Vec<PathBuf>, commands: &[&str]) -> Inhibitor {
+        Inhibitor::new(InhibitSettings {
+            files,
+            commands: commands.iter().map(|c| c.to_string()).collect(),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn checks_only_once_per_interval() {
+        let log = temp_path("interval");
+        let mut inhibitor = with_conditions(vec![], &[&format!("echo check >> {}; false", log.display())]);
+        let checks = || fs::read_to_string(&log).unwrap_or_default().lines().count();
+        let start = Instant::now();
+
+        inhibitor.update(start);
+        inhibitor.finish();
+        assert_eq!(checks(), 1);
+
+        inhibitor.update(start + Duration::from_millis(DEFAULT_INTERVAL_MS - 1));
+        inhibitor.finish();
+        assert_eq!(checks(), 1);
+
+        inhibitor.update(start + Duration::from_millis(DEFAULT_INTERVAL_MS));
+        inhibitor.finish();
+        assert_eq!(checks(), 2);
+    }
+
+    #[test]
+    fn existing_file_inhibits_rotation() {
+        let path = temp_path("file");
+        let mut inhibitor = with_conditions(vec![path.clone()], &[]);
+        let start = Instant::now();
+
+        fs::write(&path, "").unwrap();
+        inhibitor.update(start);
+        assert!(inhibitor.finish());
+        assert_eq!(inhibitor.reason(), Some(format!("Datei {} existiert", path.display()).as_str()));
+
+        fs::remove_file(&path).unwrap();
+        inhibitor.update(start + Duration::from_millis(DEFAULT_INTERVAL_MS));
+        assert!(inhibitor.finish());
+        assert!(inhibitor.reason().is_none());
+    }
+
+    #[test]
+    fn commands_inhibit_when_they_succeed() {
+        let mut inhibitor = with_conditions(vec![], &["false"]);
+        inhibitor.update(Instant::now());
+        assert!(!inhibitor.finish());
+        assert!(inhibitor.reason().is_none());
+
+        let mut inhibitor = with_conditions(vec![], &["false", "true"]);
+        inhibitor.update(Instant::now());
+        assert!(inhibitor.finish());
+        assert_eq!(inhibitor.reason(), Some("Befehl \"true\" trifft zu"));
+    }
+
+    #[test]
+    fn slow_checks_do_not_block_updates() {
+        let mut inhibitor = with_conditions(vec![], &["sleep 0.5; true"]);
+        let start = Instant::now();
+
+        assert!(!inhibitor.update(start));
+        assert!(!inhibitor.update(start + Duration::from_millis(DEFAULT_INTERVAL_MS)));
+        assert!(start.elapsed() < Duration::from_millis(200));
+        assert!(inhibitor.reason().is_none());
+
+        // Das Ergebnis wird beim ersten Aufruf nach Ende der Prüfung übernommen.
+        while inhibitor.pending.as_ref().is_some_and(|p| !p.is_finished()) {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(inhibitor.update(start + Duration::from_millis(DEFAULT_INTERVAL_MS)));
+        assert!(inhibitor.reason().is_some());
+    }
+}
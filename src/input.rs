@@ -0,0 +1,208 @@
+//! Rotiert Touchscreens und Stifteingaben zusammen mit dem Bildschirm.
+//!
+//! - Unter X11 wird die libinput-Eigenschaft "Coordinate Transformation Matrix" per `xinput` gesetzt.
+//! - Unter KDE Plasma (Wayland) wird das Gerät über die D-Bus-Schnittstelle von KWin (per `busctl`)
+//!   dem Bildschirm zugeordnet; KWin überträgt die Rotation des Bildschirms dann selbst auf die Eingaben.
+
+use std::{env, process::Command};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{command::CommandSettings, monitor::{Rotation, Transform}};
+
+
+/// Maximale Laufzeit eines Aufrufs von `xinput` bzw. `busctl` in Millisekunden.
+/// Ein hängender X-Server oder D-Bus-Dienst soll die Rotation nicht aufhalten.
+const COMMAND_TIMEOUT_MS: u64 = 2000;
+
+
+/// Legt fest, wie die Eingabegeräte rotiert werden.
+#[derive(Serialize, Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum InputMethod {
+    /// Wählt anhand von `XDG_SESSION_TYPE` zwischen `xinput` und `kwin`
+    #[default]
+    Auto,
+
+    /// X11: Transformationsmatrix per `xinput` setzen
+    Xinput,
+
+    /// KDE Plasma unter Wayland: Gerät per D-Bus dem Bildschirm zuordnen
+    Kwin,
+}
+
+/// Einstellungen für die Rotation von Eingabegeräten.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct InputMapping {
+    /// Namensmuster der Eingabegeräte, z.B. `*Touchscreen*` oder `Wacom*`.
+    /// `*` steht für beliebig viele Zeichen, Groß- und Kleinschreibung wird ignoriert.
+    pub devices: Vec<String>,
+
+    #[serde(default)]
+    pub method: InputMethod,
+
+    /// Name des Bildschirms, zu dem die Geräte gehören.
+    /// Ohne Angabe wird der erste rotierte Bildschirm verwendet.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub monitor: Option<String>,
+}
+
+impl InputMapping {
+    /// Rotiert alle passenden Eingabegeräte zur angegebenen Transformation des Bildschirms `monitor_name`.
+    pub fn apply(&self, monitor_name: &str, transform: Transform) -> Result<()> {
+        let method = match self.method {
+            InputMethod::Auto if env::var("XDG_SESSION_TYPE").is_ok_and(|t| t == "wayland") => InputMethod::Kwin,
+            InputMethod::Auto => InputMethod::Xinput,
+            method => method,
+        };
+
+        match method {
+            InputMethod::Xinput => self.apply_xinput(transform),
+            _ => self.apply_kwin(monitor_name),
+        }
+    }
+
+    /// Gibt an, ob der Gerätename auf eines der Muster passt.
+    fn matches(&self, device_name: &str) -> bool {
+        self.devices.iter().any(|pattern| wildcard_match(&pattern.to_lowercase(), &device_name.to_lowercase()))
+    }
+
+    /// Setzt die Transformationsmatrix aller passenden Geräte per `xinput`.
+    fn apply_xinput(&self, transform: Transform) -> Result<()> {
+        // Beide Aufrufe listen die Geräte in derselben Reihenfolge auf.
+        // Die IDs werden benötigt, da z.B. Stift und Radierer denselben Namen haben können.
+        let ids = run(Command::new("xinput").args(["list", "--id-only"]), "xinput")?;
+        let names = run(Command::new("xinput").args(["list", "--name-only"]), "xinput")?;
+
+        let matrix = transformation_matrix(transform).map(|value| value.to_string());
+
+        let devices = String::from_utf8_lossy(&ids).lines()
+            .zip(String::from_utf8_lossy(&names).lines())
+            .filter(|(_, name)| self.matches(name.trim()))
+            .map(|(id, _)| id.trim().to_string())
+            .collect::<Vec<_>>();
+
+        for id in devices {
+            run(Command::new("xinput")
+                .args(["set-prop", &id, "Coordinate Transformation Matrix"])
+                .args(&matrix), "xinput")?;
+        }
+
+        Ok(())
+    }
+
+    /// Ordnet alle passenden Geräte per D-Bus dem Bildschirm zu.
+    fn apply_kwin(&self, monitor_name: &str) -> Result<()> {
+        let sys_names: Vec<String> = busctl_get_property("/org/kde/KWin/InputDevice", "org.kde.KWin.InputDeviceManager", "devicesSysNames")?;
+
+        for sys_name in sys_names {
+            let path = format!("/org/kde/KWin/InputDevice/{sys_name}");
+            let name: String = busctl_get_property(&path, "org.kde.KWin.InputDevice", "name")?;
+
+            if self.matches(&name) {
+                run(Command::new("busctl")
+                    .args(["--user", "set-property", "org.kde.KWin", &path, "org.kde.KWin.InputDevice", "outputName", "s", monitor_name]), "busctl")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Berechnet die "Coordinate Transformation Matrix" (zeilenweise), die Eingaben auf dem Bildschirm
+/// in Koordinaten des gedrehten und ggf. gespiegelten Bildes umrechnet.
+fn transformation_matrix(transform: Transform) -> [i32; 9] {
+    let [a, b, c, d, e, f, g, h, i] = match transform.rotation {
+        Rotation::None => [1, 0, 0, 0, 1, 0, 0, 0, 1],
+        Rotation::Left => [0, -1, 1, 1, 0, 0, 0, 0, 1],
+        Rotation::Right => [0, 1, 0, -1, 0, 1, 0, 0, 1],
+        Rotation::Inverted => [-1, 0, 1, 0, -1, 1, 0, 0, 1],
+    };
+
+    // Das Bild wird vor der Rotation an der senkrechten Achse gespiegelt.
+    // Nach der Rotation muss die x-Koordinate daher noch zu 1 - x gespiegelt werden;
+    // das entspricht der Multiplikation mit [-1 0 1; 0 1 0; 0 0 1] von links.
+    if transform.flipped {
+        [g - a, h - b, i - c, d, e, f, g, h, i]
+    } else {
+        [a, b, c, d, e, f, g, h, i]
+    }
+}
+
+/// Liest eine Eigenschaft eines KWin-D-Bus-Objekts per `busctl` im JSON-Format.
+fn busctl_get_property<T: for<'d> Deserialize<'d>>(path: &str, interface: &str, property: &str) -> Result<T> {
+    // Stellt die Struktur der Ausgabe von `busctl --json=short get-property` dar:
+    #[derive(Deserialize)]
+    struct JsonOutput<T> {
+        data: T
+    }
+
+    let output = run(Command::new("busctl")
+        .args(["--user", "--json=short", "get-property", "org.kde.KWin", path, interface, property]), "busctl")?;
+
+    let json_output: JsonOutput<T> = serde_json::from_slice(&output)?;
+    Ok(json_output.data)
+}
+
+/// Führt einen Befehl mit Zeitlimit und ohne Wiederholungen aus und gibt seine Standardausgabe zurück.
+/// Gibt einen Fehler zurück, wenn er mit einem anderen Status als 0 endet oder zu lange läuft.
+fn run(command: &mut Command, program: &str) -> Result<Vec<u8>> {
+    let commands = CommandSettings {
+        timeout_ms: Some(COMMAND_TIMEOUT_MS),
+        retries: Some(0),
+        ..Default::default()
+    };
+
+    commands.run(command, program, true)
+}
+
+/// Vergleicht einen Text mit einem Muster, in dem `*` für beliebig viele Zeichen steht.
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+
+    // Der Text muss mit dem Teil vor dem ersten `*` beginnen ...
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else { return false };
+
+    // ... und mit dem Teil nach dem letzten `*` enden (sofern es ein `*` gibt).
+    let mut parts = parts.collect::<Vec<_>>();
+    let Some(last) = parts.pop() else { return rest.is_empty() };
+
+    // Alle Teile dazwischen müssen in dieser Reihenfolge vorkommen.
+    for part in parts {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+
+    rest.ends_with(last)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Wendet die Matrix auf einen Punkt in normierten Bildschirmkoordinaten an.
+    fn map(transform: Transform, x: i32, y: i32) -> (i32, i32) {
+        let m = transformation_matrix(transform);
+        (m[0] * x + m[1] * y + m[2], m[3] * x + m[4] * y + m[5])
+    }
+
+    #[test]
+    fn mirrored_matrices_swap_left_and_right_of_the_image() {
+        for rotation in [Rotation::None, Rotation::Left, Rotation::Right, Rotation::Inverted] {
+            let plain = Transform { rotation, flipped: false };
+            let flipped = Transform { rotation, flipped: true };
+
+            for (x, y) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                let (u, v) = map(plain, x, y);
+                assert_eq!(map(flipped, x, y), (1 - u, v));
+            }
+        }
+
+        assert_eq!(transformation_matrix(Transform { rotation: Rotation::None, flipped: true }), [-1, 0, 1, 0, 1, 0, 0, 0, 1]);
+    }
+}
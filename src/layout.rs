@@ -0,0 +1,265 @@
+//! Hält die Anordnung mehrerer Bildschirme lückenlos, wenn einer davon seine Größe ändert.
+//!
+//! Bei einer Rotation um 90° vertauschen sich Breite und Höhe eines Bildschirms;
+//! ein Anzeigeprofil kann zudem Skalierung und Auflösung ändern.
+//! Plasma verschiebt die übrigen Bildschirme dabei nicht, sodass Überlappungen oder Lücken entstehen.
+//! Liegen alle Bildschirme in einer Reihe nebeneinander oder in einer Spalte übereinander,
+//! werden sie daher in ihrer bisherigen Reihenfolge wieder direkt aneinandergereiht.
+//! Andere Anordnungen (z.B. ein Raster) werden nicht verändert.
+
+use crate::monitor::{OutputState, PlasmaMonitor, Position, Transform};
+
+
+/// Erlaubte Abweichung in Pixeln, bis zu der zwei Bildschirme noch als aneinandergrenzend gelten.
+/// Bei gebrochenen Skalierungsfaktoren entstehen Rundungsfehler.
+const EDGE_TOLERANCE: i32 = 2;
+
+
+/// Rechteck eines Bildschirms im virtuellen Desktop in logischen Pixeln,
+/// d.h. nach Anwendung von Skalierung und Rotation.
+#[derive(Clone, Copy)]
+struct Rect {
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+}
+
+impl Rect {
+    /// Berechnet das Rechteck eines Bildschirms, als hätte er die angegebene Transformation.
+    /// `size` enthält bei `kscreen-doctor` die unrotierte Auflösung des aktuellen Modus.
+    fn of(state: &OutputState, transform: Transform) -> Self {
+        let scale = if state.scale > 0.0 { state.scale } else { 1.0 };
+        let width = (state.size.width as f32 / scale).round() as i32;
+        let height = (state.size.height as f32 / scale).round() as i32;
+        let (width, height) = if transform.rotation.swaps_axes() { (height, width) } else { (width, height) };
+
+        Self { x: state.pos.x, y: state.pos.y, width, height }
+    }
+}
+
+
+/// Berechnet die Positionen, die die Bildschirme nach einer Änderung des Bildschirms `name` haben müssen,
+/// damit die Anordnung lückenlos bleibt.
+/// `new_state` ist der Zustand dieses Bildschirms nach der Änderung (Rotation, Skalierung, Auflösung).
+///
+/// Zurückgegeben werden nur Bildschirme, deren Position sich ändert.
+/// Die Liste ist leer, wenn sich die Größe des Bildschirms nicht ändert,
+/// die Anordnung weder eine Reihe noch eine Spalte ist oder die Rotation eines Bildschirms unbekannt ist.
+pub fn positions_after_change(monitors: &[PlasmaMonitor], name: &str, new_state: &OutputState) -> Vec<(String, Position)> {
+    // Nur eingeschaltete Bildschirme sind Teil der Anordnung.
+    let active: Vec<_> = monitors
+        .iter()
+        .filter_map(|m| Some((m, m.state.as_ref()?)))
+        .filter(|(_, state)| state.enabled && state.connected)
+        .collect();
+
+    // Rechtecke vor und nach der Änderung.
+    let mut before = Vec::with_capacity(active.len());
+    let mut after = Vec::with_capacity(active.len());
+
+    for (monitor, state) in &active {
+        let Some(transform) = state.transform else { return vec![] };
+        let rect = Rect::of(state, transform);
+
+        before.push((monitor.name.as_str(), rect));
+        after.push(match monitor.name == name {
+            true => Rect::of(new_state, new_state.transform.unwrap_or(transform)),
+            false => rect,
+        });
+    }
+
+    let size_changed = before
+        .iter()
+        .zip(&after)
+        .any(|((_, old), new)| (old.width, old.height) != (new.width, new.height));
+
+    if before.len() < 2 || !size_changed {
+        return vec![];
+    }
+
+    let new_positions = if let Some(order) = sorted_if_contiguous(&before, |r| (r.x, r.width)) {
+        arrange(&before, &after, &order, |r| r.x, |r| r.width, |rect, x| Position { x, y: rect.y })
+    } else if let Some(order) = sorted_if_contiguous(&before, |r| (r.y, r.height)) {
+        arrange(&before, &after, &order, |r| r.y, |r| r.height, |rect, y| Position { x: rect.x, y })
+    } else {
+        return vec![];
+    };
+
+    new_positions
+        .into_iter()
+        .zip(&before)
+        .filter(|(pos, (_, rect))| *pos != Position { x: rect.x, y: rect.y })
+        .map(|(pos, (name, _))| (name.to_string(), pos))
+        .collect()
+}
+
+/// Sortiert die Bildschirme entlang einer Achse und prüft, ob sie sich darauf nicht überlappen.
+/// `axis` liefert Anfang und Länge eines Rechtecks auf dieser Achse.
+/// Gibt die Indizes in sortierter Reihenfolge zurück, oder [`None`], wenn sich Bildschirme überlappen.
+fn sorted_if_contiguous(rects: &[(&str, Rect)], axis: impl Fn(&Rect) -> (i32, i32)) -> Option<Vec<usize>> {
+    let mut order: Vec<usize> = (0..rects.len()).collect();
+    order.sort_by_key(|&i| axis(&rects[i].1).0);
+
+    order
+        .windows(2)
+        .all(|pair| {
+            let (start, length) = axis(&rects[pair[0]].1);
+            start + length <= axis(&rects[pair[1]].1).0 + EDGE_TOLERANCE
+        })
+        .then_some(order)
+}
+
+/// Reiht die Bildschirme in der angegebenen Reihenfolge direkt aneinander,
+/// beginnend an der bisherigen Position des ersten Bildschirms.
+/// Die Position auf der anderen Achse bleibt unverändert.
+fn arrange(
+    before: &[(&str, Rect)],
+    after: &[Rect],
+    order: &[usize],
+    start: impl Fn(&Rect) -> i32,
+    length: impl Fn(&Rect) -> i32,
+    position: impl Fn(&Rect, i32) -> Position,
+) -> Vec<Position> {
+    let mut positions: Vec<Position> = before.iter().map(|(_, r)| Position { x: r.x, y: r.y }).collect();
+    let mut cursor = start(&before[order[0]].1);
+
+    for &i in order {
+        positions[i] = position(&before[i].1, cursor);
+        cursor += length(&after[i]);
+    }
+
+    positions
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::monitor::{OutputState, Rotation, Size};
+
+    use super::*;
+
+    fn monitor(name: &str, x: i32, y: i32, width: i32, height: i32, scale: f32) -> PlasmaMonitor {
+        PlasmaMonitor {
+            state: Some(OutputState {
+                enabled: true,
+                connected: true,
+                transform: Some(Transform { rotation: Rotation::None, flipped: false }),
+                pos: Position { x, y },
+                size: Size { width, height },
+                current_mode_id: String::new(),
+                modes: vec![],
+                scale,
+            }),
+            ..PlasmaMonitor::from_name(name.to_string())
+        }
+    }
+
+    /// Zustand des Bildschirms `name` nach einer Rotation, optional mit neuer Skalierung.
+    fn rotated(monitors: &[PlasmaMonitor], name: &str, rotation: Rotation, scale: Option<f32>) -> OutputState {
+        let mut state = monitors.iter().find(|m| m.name == name).unwrap().state.clone().unwrap();
+        state.transform = Some(Transform { rotation, flipped: false });
+        state.scale = scale.unwrap_or(state.scale);
+        state
+    }
+
+    fn moves(monitors: &[PlasmaMonitor], name: &str, rotation: Rotation, scale: Option<f32>) -> Vec<(String, i32, i32)> {
+        positions_after_change(monitors, name, &rotated(monitors, name, rotation, scale))
+            .into_iter()
+            .map(|(name, pos)| (name, pos.x, pos.y))
+            .collect()
+    }
+
+    fn moved(name: &str, x: i32, y: i32) -> (String, i32, i32) {
+        (name.to_string(), x, y)
+    }
+
+    #[test]
+    fn right_neighbour_follows_narrower_monitor() {
+        let monitors = [monitor("A", 0, 0, 1920, 1080, 1.0), monitor("B", 1920, 0, 1920, 1080, 1.0)];
+
+        assert_eq!(moves(&monitors, "A", Rotation::Left, None), [moved("B", 1080, 0)]);
+    }
+
+    #[test]
+    fn left_neighbour_stays_in_place() {
+        let monitors = [monitor("B", 0, 0, 1920, 1080, 1.0), monitor("A", 1920, 0, 1920, 1080, 1.0)];
+
+        assert!(moves(&monitors, "A", Rotation::Right, None).is_empty());
+    }
+
+    #[test]
+    fn monitor_below_follows_taller_monitor() {
+        let monitors = [monitor("A", 0, 0, 1920, 1080, 1.0), monitor("B", 0, 1080, 1920, 1080, 1.0)];
+
+        assert_eq!(moves(&monitors, "A", Rotation::Right, None), [moved("B", 0, 1920)]);
+    }
+
+    #[test]
+    fn monitor_above_stays_in_place() {
+        let monitors = [monitor("B", 0, 0, 1920, 1080, 1.0), monitor("A", 0, 1080, 1920, 1080, 1.0)];
+
+        assert!(moves(&monitors, "A", Rotation::Left, None).is_empty());
+    }
+
+    #[test]
+    fn middle_monitor_moves_only_its_right_neighbour() {
+        let monitors = [
+            monitor("L", 0, 0, 1920, 1080, 1.0),
+            monitor("M", 1920, 0, 1920, 1080, 1.0),
+            monitor("R", 3840, 0, 1920, 1080, 1.0),
+        ];
+
+        assert_eq!(moves(&monitors, "M", Rotation::Inverted, None), []);
+        assert_eq!(moves(&monitors, "M", Rotation::Left, None), [moved("R", 3000, 0)]);
+    }
+
+    #[test]
+    fn gaps_are_closed() {
+        let monitors = [monitor("A", 0, 0, 1920, 1080, 1.0), monitor("B", 2000, 0, 1920, 1080, 1.0)];
+
+        assert_eq!(moves(&monitors, "A", Rotation::Left, None), [moved("B", 1080, 0)]);
+    }
+
+    #[test]
+    fn rounding_errors_of_scaled_monitors_are_tolerated() {
+        // 2560 / 1.5 ergibt 1706.67; Plasma platziert den Nachbarn bei 1707 oder 1706.
+        let monitors = [monitor("A", 0, 0, 2560, 1440, 1.5), monitor("B", 1706, 0, 1920, 1080, 1.0)];
+
+        assert_eq!(moves(&monitors, "A", Rotation::Left, None), [moved("B", 960, 0)]);
+    }
+
+    #[test]
+    fn scaled_monitors_use_logical_size() {
+        let monitors = [monitor("A", 0, 0, 3840, 2160, 2.0), monitor("B", 1920, 0, 1920, 1080, 1.0)];
+
+        assert_eq!(moves(&monitors, "A", Rotation::Right, None), [moved("B", 1080, 0)]);
+
+        // Eine geänderte Skalierung ändert die Größe auch ohne Rotation.
+        assert_eq!(moves(&monitors, "A", Rotation::None, Some(1.5)), [moved("B", 2560, 0)]);
+    }
+
+    #[test]
+    fn grids_are_not_rearranged() {
+        let monitors = [
+            monitor("A", 0, 0, 1920, 1080, 1.0),
+            monitor("B", 1920, 0, 1920, 1080, 1.0),
+            monitor("C", 0, 1080, 1920, 1080, 1.0),
+            monitor("D", 1920, 1080, 1920, 1080, 1.0),
+        ];
+
+        assert!(moves(&monitors, "A", Rotation::Left, None).is_empty());
+    }
+
+    #[test]
+    fn unknown_or_disabled_monitors() {
+        let mut unknown = monitor("B", 1920, 0, 1920, 1080, 1.0);
+        unknown.state.as_mut().unwrap().transform = None;
+        assert!(moves(&[monitor("A", 0, 0, 1920, 1080, 1.0), unknown], "A", Rotation::Left, None).is_empty());
+
+        // Ausgeschaltete Bildschirme gehören nicht zur Anordnung.
+        let mut disabled = monitor("B", 1920, 0, 1920, 1080, 1.0);
+        disabled.state.as_mut().unwrap().enabled = false;
+        assert!(moves(&[monitor("A", 0, 0, 1920, 1080, 1.0), disabled], "A", Rotation::Left, None).is_empty());
+    }
+}
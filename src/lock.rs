@@ -0,0 +1,101 @@
+//! Rotationssperre, mit der die aktuelle Ausrichtung im laufenden Betrieb eingefroren werden kann.
+//!
+//! Die Sperre wird durch das Vorhandensein einer Datei dargestellt.
+//! Dadurch lässt sie sich von außen umschalten, z.B. mit `screen_rotator lock toggle`,
+//! per `SIGUSR1` an den laufenden Prozess oder durch einfaches Anlegen bzw. Löschen der Datei.
+//! Im Probelauf wird der Zustand nur im Speicher geändert (siehe [`RotationLock::in_memory`]).
+
+use std::{env, fs, io::ErrorKind, path::PathBuf, sync::{Arc, atomic::{AtomicBool, Ordering}}};
+
+use anyhow::{Result, anyhow};
+
+
+/// Name der Sperrdatei, wenn kein Pfad konfiguriert ist.
+const DEFAULT_FILE_NAME: &str = "screen_rotator.lock";
+
+
+/// Repräsentiert die Rotationssperre über den Pfad der Sperrdatei.
+#[derive(Clone)]
+pub struct RotationLock {
+    path: PathBuf,
+
+    /// Zustand, der statt der Sperrdatei verwendet wird.
+    /// Ist zwischen allen Kopien dieser Sperre geteilt.
+    memory: Option<Arc<AtomicBool>>,
+}
+
+impl RotationLock {
+    /// Erstellt eine Rotationssperre mit der angegebenen Sperrdatei.
+    /// Ohne Pfad wird die Datei im Verzeichnis `$XDG_RUNTIME_DIR` (bzw. im temporären Verzeichnis) verwendet.
+    pub fn new(path: Option<PathBuf>) -> Self {
+        let path = path.unwrap_or_else(|| {
+            env::var_os("XDG_RUNTIME_DIR")
+                .map(PathBuf::from)
+                .unwrap_or_else(env::temp_dir)
+                .join(DEFAULT_FILE_NAME)
+        });
+
+        Self { path, memory: None }
+    }
+
+    /// Erstellt eine Sperre, die mit dem aktuellen Zustand der Sperrdatei beginnt,
+    /// Änderungen aber nur im Speicher vornimmt.
+    /// Ein Probelauf darf die Sperre eines parallel laufenden Prozesses nicht verändern.
+    pub fn in_memory(&self) -> Self {
+        Self { path: self.path.clone(), memory: Some(Arc::new(AtomicBool::new(self.is_locked()))) }
+    }
+
+    /// Gibt an, ob die Rotation gesperrt ist.
+    pub fn is_locked(&self) -> bool {
+        match &self.memory {
+            Some(memory) => memory.load(Ordering::Relaxed),
+            None => self.path.exists(),
+        }
+    }
+
+    /// Sperrt oder entsperrt die Rotation.
+    pub fn set_locked(&self, locked: bool) -> Result<()> {
+        if let Some(memory) = &self.memory {
+            memory.store(locked, Ordering::Relaxed);
+            return Ok(());
+        }
+
+        let result = if locked {
+            fs::write(&self.path, "")
+        } else {
+            match fs::remove_file(&self.path) {
+                Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+                res => res,
+            }
+        };
+
+        result.map_err(|e| anyhow!("Sperrdatei {} konnte nicht geändert werden: {e}", self.path.display()))
+    }
+
+    /// Schaltet die Sperre um und gibt den neuen Zustand zurück.
+    pub fn toggle(&self) -> Result<bool> {
+        let locked = !self.is_locked();
+        self.set_locked(locked)?;
+        Ok(locked)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn in_memory_lock_leaves_file_untouched() {
+        let path = env::temp_dir().join(format!("screen_rotator_lock_{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let lock = RotationLock::new(Some(path.clone())).in_memory();
+        let shared = lock.clone();
+        assert!(!lock.is_locked());
+
+        assert!(lock.toggle().unwrap());
+        assert!(shared.is_locked());
+        assert!(!path.exists());
+    }
+}
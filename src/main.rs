@@ -13,15 +13,54 @@ mod serial;
 // Ansteuerung des Monitors; Berechnung der Richtungsvektoren
 mod monitor;
 
+// Programme zum Auflisten und Rotieren der Bildschirme (kscreen-doctor, Befehlsvorlagen)
+mod backend;
+
+// Kennung der Bildschirme aus ihrer EDID, um sie nach einem Wechsel des Anschlusses wiederzufinden
+mod edid;
+
+// Gemittelte, ruhige Messwerte für die Kalibrierung
+mod calibration;
+
+// Lückenlose Anordnung mehrerer Bildschirme nach einer Rotation
+mod layout;
+
+// Ausführen externer Programme mit Zeitlimit und Wiederholungen
+mod command;
+
+// Entscheidung, wann der Bildschirm rotiert werden soll
+mod filter;
+
+// Sperren der Rotation im laufenden Betrieb
+mod lock;
+
+// Vorübergehendes Anhalten der Rotation, z.B. während eine Präsentation läuft
+mod inhibit;
+
+// Benutzerdefinierte Befehle nach einer Rotation
+mod hooks;
+
+// Desktop-Benachrichtigungen bei Rotation, Sperre und Fehlern
+mod notify;
+
+// Rotation von Touchscreens und Stifteingaben
+mod input;
+
 // Stabilisierung eines Bildes in einem Fenster
 mod rotate_image;
 
+// Beenden, Neuladen der Konfiguration und Umschalten der Sperre über Signale
+mod signals;
+
+use std::process::ExitCode;
+
 use anyhow::Result;
 use clap::Parser;
 
 
-fn main() -> Result<()> {
-    // lese Eingabeargumente und führe entsprechenden Befehl aus
+fn main() -> Result<ExitCode> {
+    // lese Eingabeargumente und führe entsprechenden Befehl aus;
+    // der Exit-Status wird erst hier zurückgegeben, damit vorher alle Ressourcen freigegeben werden
     let args = crate::args::Args::parse();
     args.run_selected_mode()
 }
@@ -1,19 +1,36 @@
-//! Enthält alle Methoden, die zur Auflistung und Rotation des Bildschirms unter KDE Plasma benötigt werden,
+//! Enthält alle Methoden, die zur Auflistung und Rotation des Bildschirms unter KDE Plasma
+//! (oder über benutzerdefinierte Befehlsvorlagen, siehe [`backend`](crate::backend)) benötigt werden,
 //! sowie das [`OrientationVectors`]-Struct, das die Richtungsvektoren repräsentiert.
 
-use std::{collections::BTreeMap, fmt::Display, process::{Command, ExitStatus, Stdio}};
+use std::{collections::BTreeMap, fmt::Display, ops::{Add, Sub}, process::Command, str::FromStr, thread::{self, JoinHandle}, time::{Duration, Instant, SystemTime, UNIX_EPOCH}};
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
 use glam::Vec3;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 
-use crate::{serial::SerialReader};
+use crate::{backend::{BackendKind, CommandTemplates, DisplayBackend}, command::FailurePolicy, edid::{self, EdidIdentity}, filter::{self, MotionDetector, MotionSettings, RotationDebouncer}, hooks::{HookEvent, HookSettings}, inhibit::{InhibitSettings, Inhibitor}, input::InputMapping, layout, lock::RotationLock, notify::{Category, NotificationSettings}, serial::{SensorDisconnected, SerialReader}, signals::SignalFlags};
+
+
+/// Zeitabstand, in dem die tatsächliche Rotation des Bildschirms erneut abgefragt wird,
+/// um manuelle Änderungen (z.B. in den Systemeinstellungen) zu erkennen.
+const RESYNC_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Standardwert für die Zeit in Sekunden, die beim Start auf einen fehlenden Bildschirm gewartet wird.
+pub const DEFAULT_MONITOR_WAIT_SECONDS: u64 = 10;
+
+/// Zeitabstand, in dem beim Start erneut nach einem fehlenden Bildschirm gesucht wird.
+const AVAILABILITY_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Anzahl der Messwerte, die in [`rotate_once`] gemittelt werden.
+const ONE_SHOT_SAMPLES: usize = 20;
 
 
 /// Auflistung aller Rotationen, die `kscreen-doctor` unterstützt.
-#[derive(PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Clone, Copy)]
+/// Der Wert entspricht der Anzahl an Vierteldrehungen im Uhrzeigersinn.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Clone, Copy, Default)]
 #[repr(u8)]
 pub enum Rotation {
+    #[default]
     None = 0,
     Right = 1,
     Inverted = 2,
@@ -21,8 +38,29 @@ pub enum Rotation {
 }
 
 impl Rotation {
+    /// Erzeugt eine Rotation aus der Anzahl an Vierteldrehungen im Uhrzeigersinn.
+    fn from_quarter_turns(turns: u8) -> Self {
+        match turns % 4 {
+            0 => Self::None,
+            1 => Self::Right,
+            2 => Self::Inverted,
+            _ => Self::Left,
+        }
+    }
+
+    /// Gibt an, ob es sich um die Ausgangslage handelt.
+    /// Wird benötigt, um einen Versatz von 0° nicht in die Konfigurationsdatei zu schreiben.
+    pub fn is_none(&self) -> bool {
+        *self == Self::None
+    }
+
+    /// Gibt an, ob Breite und Höhe des Bildschirms durch diese Rotation vertauscht werden.
+    pub fn swaps_axes(self) -> bool {
+        matches!(self, Self::Left | Self::Right)
+    }
+
     /// Gibt die Rotation im von `kscreen-doctor` erwarteten Format zurück.
-    fn to_str(self) -> &'static str {
+    pub fn to_str(self) -> &'static str {
         match self {
             Self::None => "none",
             Self::Left => "left",
@@ -30,6 +68,19 @@ impl Rotation {
             Self::Inverted => "inverted",
         }
     }
+
+    /// Gibt die Rotation im Uhrzeigersinn in Grad zurück (0, 90, 180 oder 270).
+    pub fn to_degrees(self) -> u16 {
+        self as u16 * 90
+    }
+
+    /// Gibt die Rotation im von `xrandr` erwarteten Format zurück.
+    pub fn to_xrandr_str(self) -> &'static str {
+        match self {
+            Self::None => "normal",
+            rotation => rotation.to_str(),
+        }
+    }
 }
 
 /// Ermöglicht die Nutzung von [`Display`] im [`format!`]-Macro.
@@ -39,34 +90,396 @@ impl Display for Rotation {
     }
 }
 
+/// Spiegelung eines Bildschirms, z.B. wenn er über einen Spiegel betrachtet oder von hinten projiziert wird.
+/// Die Spiegelung bezieht sich immer auf das Bild, wie es der Betrachter sieht, unabhängig von der Rotation.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Mirror {
+    /// Spiegelung an der senkrechten Achse (links und rechts vertauscht)
+    Horizontal,
+
+    /// Spiegelung an der waagerechten Achse (oben und unten vertauscht)
+    Vertical,
+}
+
+impl Mirror {
+    /// Berechnet die Transformation, die das um `rotation` gedrehte Bild zusätzlich spiegelt.
+    ///
+    /// Gespiegelte Transformationen spiegeln zuerst an der senkrechten Achse und rotieren danach.
+    /// Eine Spiegelung nach der Rotation entspricht daher einer Spiegelung vor der entgegengesetzten Rotation;
+    /// eine vertikale Spiegelung ist eine horizontale mit zusätzlicher halber Drehung.
+    fn apply(self, rotation: Rotation) -> Transform {
+        let rotation = match self {
+            Self::Horizontal => Rotation::None - rotation,
+            Self::Vertical => Rotation::Inverted - rotation,
+        };
+
+        Transform { rotation, flipped: true }
+    }
+
+    /// Gegenstück zu [`apply`](Self::apply): ermittelt die Rotation, aus der eine Transformation entstanden ist.
+    /// Ist die Transformation nicht gespiegelt, passt sie nicht zu dieser Spiegelung.
+    fn rotation_of(self, transform: Transform) -> Option<Rotation> {
+        // Beide Abbildungen sind ihre eigene Umkehrung.
+        transform.flipped.then(|| self.apply(transform.rotation).rotation)
+    }
+}
+
+
+/// Transformation, die tatsächlich an das Anzeigeprogramm übergeben wird:
+/// eine Rotation, optional mit vorheriger Spiegelung an der senkrechten Achse.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Transform {
+    pub rotation: Rotation,
+    pub flipped: bool,
+}
+
+impl Transform {
+    /// Konvertiert den Wert aus der JSON-Ausgabe von `kscreen-doctor` zu einer [`Transform`].
+    /// `kscreen-doctor` kodiert die Transformationen als Bitflags (1, 2, 4 und 8, gespiegelt 16, 32, 64 und 128).
+    fn from_kscreen_value(value: u8) -> Option<Self> {
+        let (rotation, flipped) = match value {
+            1 => (Rotation::None, false),
+            2 => (Rotation::Left, false),
+            4 => (Rotation::Inverted, false),
+            8 => (Rotation::Right, false),
+            16 => (Rotation::None, true),
+            32 => (Rotation::Left, true),
+            64 => (Rotation::Inverted, true),
+            128 => (Rotation::Right, true),
+            _ => return None,
+        };
+
+        Some(Self { rotation, flipped })
+    }
+
+    /// Gibt die Transformation im von `kscreen-doctor` erwarteten Format zurück.
+    fn to_kscreen_str(self) -> &'static str {
+        match (self.flipped, self.rotation) {
+            (false, rotation) => rotation.to_str(),
+            (true, Rotation::None) => "flipped",
+            (true, Rotation::Left) => "flipped90",
+            (true, Rotation::Inverted) => "flipped180",
+            (true, Rotation::Right) => "flipped270",
+        }
+    }
+
+    /// Gibt die Spiegelung im von `xrandr --reflect` erwarteten Format zurück.
+    pub fn to_xrandr_reflect_str(self) -> &'static str {
+        if self.flipped { "x" } else { "normal" }
+    }
+}
+
+/// Ermöglicht die Nutzung von [`Display`] im [`format!`]-Macro.
+impl Display for Transform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.to_kscreen_str())
+    }
+}
+
+
+/// Liest eine Rotation im Format von `kscreen-doctor`, `xrandr` oder in Grad ein.
+impl FromStr for Rotation {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" | "normal" | "0" => Ok(Self::None),
+            "right" | "90" => Ok(Self::Right),
+            "inverted" | "180" => Ok(Self::Inverted),
+            "left" | "270" => Ok(Self::Left),
+            _ => bail!("Unbekannte Rotation \"{s}\""),
+        }
+    }
+}
+
+/// Addiert zwei Rotationen, z.B. die Rotation des Sensors und den Versatz eines Bildschirms.
+impl Add for Rotation {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self::from_quarter_turns(self as u8 + rhs as u8)
+    }
+}
+
+/// Subtrahiert zwei Rotationen, z.B. den Versatz eines Bildschirms von seiner tatsächlichen Rotation.
+impl Sub for Rotation {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self::from_quarter_turns(self as u8 + 4 - rhs as u8)
+    }
+}
+
 
 /// Repräsentiert einen Monitor, wie ihn `kscreen-doctor` zurückliefert.
 /// Implementiert [`Deserialize`], da dieses Struct einem Eintrag im `outputs`-Array der `kscreen-doctor`-Ausgabe entspricht.
 #[derive(Serialize, Deserialize, Clone)]
 pub struct PlasmaMonitor {
     pub name: String,
+
+    /// Rotation dieses Bildschirms relativ zum Sensor,
+    /// z.B. wenn mehrere Bildschirme unterschiedlich ausgerichtet zusammen montiert sind.
+    #[serde(default, skip_serializing_if = "Rotation::is_none")]
+    pub offset: Rotation,
+
+    /// Anzeigeeinstellungen, die zusammen mit der jeweiligen Rotation angewendet werden.
+    /// Die Rotation bezieht sich auf den Bildschirm selbst, nicht auf den Sensor.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<Rotation, DisplayProfile>,
+
+    /// Spiegelung, die zusätzlich zu jeder Rotation angewendet wird.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mirror: Option<Mirror>,
+
+    /// Kennung des Geräts laut EDID.
+    /// Anders als der Anschlussname bleibt sie gleich, wenn Kabel getauscht oder Docks neu verbunden werden.
+    /// In der Ausgabe von `kscreen-doctor -j` ist sie, sofern vorhanden, Base64-kodiert.
+    #[serde(default, deserialize_with = "edid::deserialize_config_or_kscreen", skip_serializing_if = "Option::is_none")]
+    pub edid: Option<EdidIdentity>,
+
+    /// Aktueller Zustand des Bildschirms laut `kscreen-doctor -j`.
+    /// Ist [`None`], wenn der Monitor aus der Konfiguration oder einem Eingabeargument stammt
+    /// oder wenn benutzerdefinierte Befehle verwendet werden.
+    /// Der Zustand ändert sich laufend und wird daher nicht in der Konfigurationsdatei gespeichert.
+    #[serde(flatten, skip_serializing)]
+    pub state: Option<OutputState>,
+}
+
+/// Zustand eines Bildschirms, wie ihn `kscreen-doctor -j` ausgibt.
+/// Fehlt eines der Felder (wie in der Konfigurationsdatei), ist der gesamte Zustand [`None`].
+#[derive(Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct OutputState {
+    pub enabled: bool,
+    pub connected: bool,
+
+    /// Aktuelle Transformation; [`None`], wenn `kscreen-doctor` einen unbekannten Wert liefert.
+    #[serde(rename = "rotation", deserialize_with = "deserialize_kscreen_transform")]
+    pub transform: Option<Transform>,
+
+    /// Position der oberen linken Ecke im virtuellen Bildschirm
+    pub pos: Position,
+
+    /// Größe des Bildschirms in Pixeln
+    pub size: Size,
+
+    /// ID des aktuell verwendeten Modus (siehe [`modes`](Self::modes))
+    pub current_mode_id: String,
+
+    /// Alle Modi (Auflösung + Bildwiederholrate), die der Bildschirm unterstützt
+    #[serde(default)]
+    pub modes: Vec<OutputMode>,
+
+    /// Skalierungsfaktor (z.B. 1.25 für 125 %)
+    pub scale: f32,
+}
+
+impl OutputState {
+    /// Gibt den aktuell verwendeten Modus zurück, sofern dieser bekannt ist.
+    pub fn current_mode(&self) -> Option<&OutputMode> {
+        self.modes.iter().find(|mode| mode.id == self.current_mode_id)
+    }
+}
+
+/// Anzeigeeinstellungen für eine bestimmte Rotation, z.B. 125 % Skalierung im Hochformat.
+/// Nicht gesetzte Felder bleiben unverändert.
+/// Profile werden nur unter Plasma angewendet, da Befehlsvorlagen keine Platzhalter dafür haben.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct DisplayProfile {
+    /// Skalierungsfaktor (z.B. 1.25 für 125 %)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scale: Option<f32>,
+
+    /// Modus als Auflösung (`2560x1440`), Auflösung mit Bildwiederholrate (`2560x1440@60`) oder Modus-ID
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
+
+    /// Bildwiederholrate in Hz.
+    /// Ohne `mode` wird dazu die aktuelle Auflösung beibehalten.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh_rate: Option<f32>,
+
+    /// Macht den Bildschirm zum primären Bildschirm.
+    /// `false` hat keine Wirkung, da Plasma nur das Setzen eines primären Bildschirms erlaubt.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub primary: Option<bool>,
+
+    /// Overscan in Prozent (0 bis 100)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub overscan: Option<u8>,
+}
+
+impl DisplayProfile {
+    /// Erzeugt die Argumente für `kscreen-doctor`, die dieses Profil auf den Bildschirm `name` anwenden.
+    /// Der aktuelle Zustand wird benötigt, um bei einer reinen Änderung der Bildwiederholrate die Auflösung zu kennen.
+    fn kscreen_args(&self, name: &str, current: Option<&OutputState>) -> Vec<String> {
+        let mut args = Vec::new();
+
+        if let Some(scale) = self.scale {
+            args.push(format!("output.{name}.scale.{scale}"));
+        }
+
+        let resolution = match &self.mode {
+            Some(mode) => Some(mode.clone()),
+            None if self.refresh_rate.is_some() => current.and_then(OutputState::current_mode).map(|mode| mode.name.clone()),
+            None => None,
+        };
+
+        match (resolution, self.refresh_rate) {
+            (Some(resolution), Some(rate)) => {
+                let resolution = resolution.split('@').next().unwrap_or_default();
+                args.push(format!("output.{name}.mode.{resolution}@{rate}"));
+            }
+            (Some(mode), None) => args.push(format!("output.{name}.mode.{mode}")),
+            (None, Some(_)) => eprintln!("Aktuelle Auflösung von \"{name}\" unbekannt, Bildwiederholrate wird nicht geändert"),
+            (None, None) => {}
+        }
+
+        if self.primary == Some(true) {
+            args.push(format!("output.{name}.primary"));
+        }
+
+        if let Some(overscan) = self.overscan {
+            args.push(format!("output.{name}.overscan.{}", overscan.min(100)));
+        }
+
+        args
+    }
+
+    /// Überträgt Skalierung und Auflösung dieses Profils auf einen Zustand,
+    /// damit die Anordnung der Bildschirme mit der neuen Größe berechnet werden kann.
+    fn apply_to(&self, state: &mut OutputState) {
+        if let Some(scale) = self.scale {
+            state.scale = scale;
+        }
+
+        // Der Modus kann auch als ID angegeben sein; dann wird sein Name nachgeschlagen.
+        let mode_name = self.mode.as_deref().map(|mode| {
+            state.modes.iter().find(|m| m.id == mode).map_or(mode, |m| m.name.as_str())
+        });
+
+        if let Some((width, height)) = mode_name.and_then(parse_resolution) {
+            state.size = Size { width, height };
+        }
+    }
+}
+
+/// Liest die Auflösung aus einem Modusnamen wie `2560x1440@60`.
+fn parse_resolution(mode: &str) -> Option<(i32, i32)> {
+    let (width, height) = mode.split('@').next()?.split_once('x')?;
+    Some((width.parse().ok()?, height.parse().ok()?))
+}
+
+/// Position eines Bildschirms in Pixeln.
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub x: i32,
+    pub y: i32,
+}
+
+/// Größe eines Bildschirms in Pixeln.
+#[derive(Deserialize, Clone, Copy)]
+pub struct Size {
+    pub width: i32,
+    pub height: i32,
+}
+
+/// Ein Anzeigemodus, wie ihn `kscreen-doctor -j` ausgibt.
+#[derive(Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct OutputMode {
+    pub id: String,
+
+    /// Name des Modus, z.B. `1920x1080@60`
+    pub name: String,
+}
+
+/// Deserialisiert die als Bitflag kodierte Transformation aus der `kscreen-doctor`-Ausgabe.
+fn deserialize_kscreen_transform<'d, D: Deserializer<'d>>(deserializer: D) -> Result<Option<Transform>, D::Error> {
+    let value = u8::deserialize(deserializer)?;
+    Ok(Transform::from_kscreen_value(value))
 }
 
 /// Wird von [`select_monitor`](crate::args::Args::select_monitor) benötigt.
+/// Ist der Zustand des Bildschirms bekannt, werden Modus, Position, Skalierung und Rotation mit angezeigt.
 #[allow(clippy::to_string_trait_impl)]
 impl ToString for PlasmaMonitor {
     fn to_string(&self) -> String {
-        self.name.clone()
+        let Some(state) = &self.state else { return self.name.clone() };
+
+        let mode = state.current_mode().map_or("?", |mode| mode.name.as_str());
+        let rotation = state.transform.map_or("?", Transform::to_kscreen_str);
+        let status = match (state.connected, state.enabled) {
+            (false, _) => " [getrennt]",
+            (true, false) => " [deaktiviert]",
+            (true, true) => "",
+        };
+
+        format!(
+            "{name} ({w}x{h} bei {x},{y}, Modus {mode}, Skalierung {scale}, Rotation {rotation}){status}",
+            name = self.name,
+            w = state.size.width,
+            h = state.size.height,
+            x = state.pos.x,
+            y = state.pos.y,
+            scale = state.scale,
+        )
     }
 }
 
 impl PlasmaMonitor {
-    /// Ruft `kscreen-doctor -j` auf, um die Namen aller verbundenen Bildschirme zu ermitteln.
-    pub fn list() -> Result<Vec<Self>> {
-        let output = Command::new("kscreen-doctor")
-            .arg("-j")
-            .stdout(Stdio::piped())
-            .stderr(Stdio::inherit())
-            .output()?;
+    /// Erzeugt einen Monitor mit dem angegebenen Namen, dessen Zustand noch unbekannt ist.
+    pub fn from_name(name: String) -> Self {
+        Self { name, offset: Rotation::None, profiles: BTreeMap::new(), mirror: None, edid: None, state: None }
+    }
 
-        check_exit_status(output.status)?;
+    /// Liest einen Bildschirm im Format `NAME` oder `NAME:VERSATZ` ein, z.B. `DP-2:right`.
+    pub fn from_arg(arg: &str) -> Result<Self> {
+        match arg.rsplit_once(':') {
+            Some((name, offset)) => Ok(Self { offset: offset.parse()?, ..Self::from_name(name.to_string()) }),
+            None => Ok(Self::from_name(arg.to_string())),
+        }
+    }
 
-        if output.stdout.is_empty() {
+    /// Übernimmt Profile, Spiegelung und EDID des gleichnamigen gespeicherten Bildschirms.
+    /// Neu per Eingabeargument oder Auswahl angegebene Bildschirme sollen diese Einstellungen beim Speichern nicht verlieren.
+    /// Der Versatz stammt weiterhin aus dem Eingabeargument.
+    pub fn keep_saved_settings(&mut self, saved: &[PlasmaMonitor]) {
+        if let Some(saved) = saved.iter().find(|saved| saved.name == self.name) {
+            self.profiles = saved.profiles.clone();
+            self.mirror = saved.mirror;
+            self.edid = saved.edid.clone();
+        }
+    }
+
+    /// Ermittelt die Namen aller verbundenen Bildschirme.
+    /// Unter Plasma wird dazu `kscreen-doctor -j` aufgerufen, ansonsten der benutzerdefinierte Befehl,
+    /// dessen Ausgabe einen Bildschirmnamen pro Zeile enthält.
+    pub fn list(backend: &DisplayBackend) -> Result<Vec<Self>> {
+        let templates = match &backend.kind {
+            BackendKind::KScreenDoctor => return Self::list_kscreen_doctor(backend),
+            BackendKind::Custom(templates) => templates,
+            BackendKind::DryRun => return Ok(vec![]),
+        };
+
+        let stdout = backend.run(&mut CommandTemplates::build_command(templates.list_template()?, None, None)?, true)?;
+
+        let monitors = String::from_utf8_lossy(&stdout)
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(|name| Self::from_name(name.to_string()))
+            .collect();
+
+        Ok(monitors)
+    }
+
+    /// Ruft `kscreen-doctor -j` auf, um die Namen aller verbundenen Bildschirme zu ermitteln.
+    fn list_kscreen_doctor(backend: &DisplayBackend) -> Result<Vec<Self>> {
+        let stdout = backend.run(Command::new("kscreen-doctor").arg("-j"), true)?;
+
+        if stdout.is_empty() {
             Ok(vec![])
         } else {
             // Stellt die Struktur der Ausgabe von `kscreen-doctor -j` dar:
@@ -75,39 +488,365 @@ impl PlasmaMonitor {
                 outputs: Vec<PlasmaMonitor>
             }
 
-            let json_output: JsonOutput = serde_json::from_slice(&output.stdout)?;
+            let json_output: JsonOutput = serde_json::from_slice(&stdout)?;
             Ok(json_output.outputs)
         }
     }
 
     /// Ruft `kscreen-doctor -o` auf, um eine menschenlesbare Liste mit Details zu allen verbundenen Displays auszugeben.
+    /// Bei benutzerdefinierten Befehlen wird stattdessen die Ausgabe des Befehls zum Auflisten angezeigt.
     /// Die Ausgabe wird direkt an `stdout` weitergeleitet.
-    pub fn show_details() -> Result<()> {
-        let status = Command::new("kscreen-doctor")
-            .arg("-o")
-            .stdout(Stdio::inherit())
-            .stderr(Stdio::inherit())
-            .status()?;
+    pub fn show_details(backend: &DisplayBackend) -> Result<()> {
+        let mut command = match &backend.kind {
+            BackendKind::KScreenDoctor => {
+                let mut command = Command::new("kscreen-doctor");
+                command.arg("-o");
+                command
+            }
+            BackendKind::Custom(templates) => CommandTemplates::build_command(templates.list_template()?, None, None)?,
+            BackendKind::DryRun => return Ok(()),
+        };
 
-        check_exit_status(status)
+        backend.run(&mut command, false)?;
+        Ok(())
     }
 
     /// Rotiert diesen Bildschirm zur angegebenen Ausrichtung.
-    pub fn rotate(&self, rotation: Rotation) -> Result<()> {
-        let status = Command::new("kscreen-doctor")
-            .arg(format!("output.{o}.rotation.{rotation}", o = self.name))
-            .stdout(Stdio::inherit())
-            .stderr(Stdio::inherit())
-            .status()?;
+    ///
+    /// Unter Plasma wird im selben Aufruf das Anzeigeprofil für diese Rotation angewendet (siehe [`profiles`](Self::profiles)).
+    /// Ändert sich dadurch die Größe des Bildschirms, werden außerdem die übrigen Bildschirme verschoben (siehe [`layout`]).
+    pub fn rotate(&self, backend: &DisplayBackend, rotation: Rotation) -> Result<()> {
+        let transform = self.transform_for(rotation);
 
-        check_exit_status(status)
+        let mut command = match &backend.kind {
+            BackendKind::KScreenDoctor => {
+                let mut command = Command::new("kscreen-doctor");
+                command.arg(format!("output.{o}.rotation.{transform}", o = self.name));
+
+                // Kann der aktuelle Zustand nicht ermittelt werden, wird trotzdem rotiert.
+                let monitors = Self::list_kscreen_doctor(backend).unwrap_or_else(|e| {
+                    eprintln!("Anordnung der Bildschirme konnte nicht ermittelt werden: {e}");
+                    vec![]
+                });
+                let current = monitors.iter().find(|m| m.name == self.name).and_then(|m| m.state.as_ref());
+                let profile = self.profiles.get(&rotation);
+
+                if let Some(profile) = profile {
+                    command.args(profile.kscreen_args(&self.name, current));
+                }
+
+                if let Some(current) = current {
+                    let mut new_state = current.clone();
+                    new_state.transform = Some(transform);
+                    if let Some(profile) = profile {
+                        profile.apply_to(&mut new_state);
+                    }
+
+                    for (name, pos) in layout::positions_after_change(&monitors, &self.name, &new_state) {
+                        command.arg(format!("output.{name}.position.{},{}", pos.x, pos.y));
+                    }
+                }
+
+                command
+            }
+            BackendKind::Custom(templates) => CommandTemplates::build_command(&templates.rotate, Some(&self.name), Some(transform))?,
+            BackendKind::DryRun => return Ok(()),
+        };
+
+        backend.run(&mut command, false)?;
+        Ok(())
+    }
+
+    /// Prüft, ob alle Bildschirme vorhanden und verbunden sind.
+    ///
+    /// Fehlt ein Bildschirm oder ist er getrennt, wird bis zu `timeout` gewartet,
+    /// da z.B. ein Dock gerade erst verbunden wird.
+    /// Danach wird ein Fehler zurückgegeben, der ähnliche Namen als Vorschlag enthält.
+    /// Lassen sich die Bildschirme nicht auflisten (Befehlsvorlagen ohne `list`, Probelauf), wird nichts geprüft.
+    pub fn wait_until_available(monitors: &[Self], backend: &DisplayBackend, timeout: Duration, signals: &SignalFlags) -> Result<()> {
+        if !backend.can_list() {
+            return Ok(());
+        }
+
+        let start = Instant::now();
+        let mut announced = false;
+
+        loop {
+            let available = Self::list(backend)?;
+            let missing: Vec<_> = monitors
+                .iter()
+                .filter(|m| !available.iter().any(|a| a.name == m.name && a.state.as_ref().is_none_or(|s| s.connected)))
+                .collect();
+
+            if missing.is_empty() {
+                return Ok(());
+            }
+
+            if start.elapsed() >= timeout || signals.should_terminate() {
+                let reasons: Vec<_> = missing.iter().map(|m| describe_missing(&m.name, &available)).collect();
+                bail!("{}", reasons.join("\n"));
+            }
+
+            if !announced {
+                let names: Vec<_> = missing.iter().map(|m| format!("\"{}\"", m.name)).collect();
+                eprintln!("Warte bis zu {} s auf Bildschirm {}", timeout.as_secs(), names.join(", "));
+                announced = true;
+            }
+
+            thread::sleep(AVAILABILITY_POLL_INTERVAL);
+        }
+    }
+
+    /// Ermittelt für alle angegebenen Bildschirme den aktuellen Anschluss anhand ihrer EDID.
+    ///
+    /// Wird ein Bildschirm mit passender EDID gefunden, wird sein Name auf den aktuellen Anschluss gesetzt.
+    /// Andernfalls wird mit einer Warnung der bisherige Name verwendet.
+    /// Bildschirme ohne gespeicherte EDID übernehmen diese vom gleichnamigen Anschluss,
+    /// damit sie beim nächsten Speichern der Konfiguration erhalten bleibt.
+    /// Die EDID wird unter `/sys/class/drm` gesucht und, falls der Anschluss dort anders heißt,
+    /// aus der Ausgabe von `kscreen-doctor -j` übernommen.
+    /// Lassen sich die Bildschirme nicht auflisten, passiert nichts.
+    ///
+    /// Gibt die Umbenennungen als Paare aus altem und neuem Namen zurück,
+    /// damit Verweise auf den alten Namen (z.B. [`InputMapping::monitor`]) angepasst werden können.
+    pub fn resolve_connectors(monitors: &mut [Self], backend: &DisplayBackend) -> Result<Vec<(String, String)>> {
+        // Benutzerdefinierte Befehle können Anschlüsse anders benennen als der Kernel (z.B. `HDMI-1` unter X11).
+        let hint = match &backend.kind {
+            BackendKind::Custom(_) => ", Anschlussnamen müssen denen unter /sys/class/drm entsprechen (z.B. HDMI-A-1 statt HDMI-1)",
+            _ => "",
+        };
+
+        if !backend.can_list() {
+            if let BackendKind::Custom(_) = backend.kind {
+                for monitor in monitors.iter().filter(|m| m.edid.is_some()) {
+                    eprintln!("Warnung: Ohne Befehl zum Auflisten kann \"{}\" nicht anhand der EDID gefunden werden", monitor.name);
+                }
+            }
+            return Ok(vec![]);
+        }
+
+        let connected: Vec<_> = Self::list(backend)?
+            .into_iter()
+            .filter_map(|m| {
+                let edid = EdidIdentity::read(&m.name).or(m.edid)?;
+                (!edid.is_empty()).then_some((m.name, edid))
+            })
+            .collect();
+
+        let mut renames = Vec::new();
+        for monitor in monitors {
+            let Some(edid) = monitor.edid.clone() else {
+                monitor.edid = connected.iter().find(|(name, _)| *name == monitor.name).map(|(_, edid)| edid.clone());
+                continue;
+            };
+
+            let matches: Vec<_> = connected.iter().filter(|(_, found)| *found == edid).map(|(name, _)| name).collect();
+
+            match matches.as_slice() {
+                [found] if **found != monitor.name => {
+                    eprintln!("Bildschirm {edid} ist jetzt an \"{found}\" statt \"{}\" angeschlossen", monitor.name);
+                    renames.push((monitor.name.clone(), found.to_string()));
+                    monitor.name = found.to_string();
+                }
+                [_] => {}
+                [] => eprintln!("Warnung: Kein Bildschirm mit der EDID {edid} gefunden, verwende \"{}\"{hint}", monitor.name),
+                // Identische Bildschirme ohne Seriennummer lassen sich nur über den Anschluss unterscheiden.
+                _ => if !matches.iter().any(|name| **name == monitor.name) {
+                    eprintln!("Warnung: Mehrere Bildschirme mit der EDID {edid} gefunden, verwende \"{}\"", monitor.name);
+                },
+            }
+        }
+
+        Ok(renames)
+    }
+
+    /// Übernimmt den Zustand des gleichnamigen Bildschirms aus der Ausgabe von [`list`](Self::list).
+    fn update_state_from(&mut self, monitors: &[Self]) -> Result<()> {
+        let monitor = monitors
+            .iter()
+            .find(|m| m.name == self.name)
+            .ok_or_else(|| anyhow!("Bildschirm \"{}\" wurde nicht gefunden", self.name))?;
+
+        self.state = monitor.state.clone();
+        Ok(())
+    }
+
+    /// Gibt die Transformation zurück, mit der dieser Bildschirm zur angegebenen Rotation gedreht wird.
+    /// Ist eine Spiegelung konfiguriert, wird sie zusätzlich angewendet.
+    pub fn transform_for(&self, rotation: Rotation) -> Transform {
+        match self.mirror {
+            Some(mirror) => mirror.apply(rotation),
+            None => Transform { rotation, flipped: false },
+        }
+    }
+
+    /// Gibt die zuletzt abgefragte Rotation dieses Bildschirms zurück, sofern bekannt.
+    /// Eine konfigurierte Spiegelung wird dabei herausgerechnet.
+    /// Passt die Transformation nicht zur Spiegelung, gilt die Rotation als unbekannt.
+    pub fn current_rotation(&self) -> Option<Rotation> {
+        let transform = self.state.as_ref()?.transform?;
+
+        match self.mirror {
+            Some(mirror) => mirror.rotation_of(transform),
+            None => (!transform.flipped).then_some(transform.rotation),
+        }
+    }
+}
+
+
+/// Beschreibt, warum ein Bildschirm nicht verfügbar ist, und schlägt ähnliche Namen vor.
+fn describe_missing(name: &str, available: &[PlasmaMonitor]) -> String {
+    if available.iter().any(|m| m.name == name) {
+        return format!("Bildschirm \"{name}\" ist nicht verbunden");
+    }
+
+    // Als ähnlich gelten Namen mit wenigen Tippfehlern oder solche, die den anderen enthalten (z.B. `DP2` und `DP-2`).
+    let lowercase = name.to_lowercase();
+    let suggestions: Vec<_> = available
+        .iter()
+        .map(|m| m.name.as_str())
+        .filter(|candidate| {
+            let candidate = candidate.to_lowercase();
+            edit_distance(&lowercase, &candidate) <= 2 || candidate.contains(&lowercase) || lowercase.contains(&candidate)
+        })
+        .collect();
+
+    match suggestions.as_slice() {
+        [] => {
+            let names: Vec<_> = available.iter().map(|m| m.name.as_str()).collect();
+            format!("Bildschirm \"{name}\" wurde nicht gefunden. Verfügbar: {}", names.join(", "))
+        }
+        _ => format!("Bildschirm \"{name}\" wurde nicht gefunden. Meinten Sie {}?", suggestions.join(", ")),
+    }
+}
+
+/// Berechnet die Levenshtein-Distanz zwischen zwei Zeichenketten,
+/// d.h. die minimale Anzahl an Einfügungen, Löschungen und Ersetzungen.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+
+/// Mehrere Bildschirme, die gemeinsam montiert sind und zusammen anhand eines Sensors rotiert werden.
+///
+/// Rotationen werden dabei immer relativ zum Sensor angegeben;
+/// jeder Bildschirm wird zusätzlich um seinen [`offset`](PlasmaMonitor::offset) gedreht.
+/// Alle Abfragen und Rotationen laufen über [`DisplayBackend::run`] und damit nacheinander,
+/// sodass mehrere Gruppen in eigenen Threads laufen können.
+pub struct MonitorGroup {
+    pub monitors: Vec<PlasmaMonitor>,
+}
+
+impl MonitorGroup {
+    /// Fragt den Zustand aller Bildschirme ab.
+    /// Unter Plasma genügt dafür ein einziger Aufruf von `kscreen-doctor`.
+    pub fn refresh_state(&mut self, backend: &DisplayBackend) -> Result<()> {
+        if !backend.reports_state() {
+            self.monitors.iter_mut().for_each(|m| m.state = None);
+            return Ok(());
+        }
+
+        let list = PlasmaMonitor::list(backend)?;
+        self.monitors.iter_mut().try_for_each(|m| m.update_state_from(&list))
+    }
+
+    /// Gibt die Rotation des Sensors zurück, die zum aktuellen Zustand der Bildschirme passt.
+    /// Ist die Rotation eines Bildschirms unbekannt oder passen die Bildschirme nicht zueinander, wird [`None`] zurückgegeben.
+    pub fn current_rotation(&self) -> Option<Rotation> {
+        let mut rotations = self.monitors.iter().map(|m| m.current_rotation().map(|r| r - m.offset));
+        let first = rotations.next()??;
+
+        rotations.all(|r| r == Some(first)).then_some(first)
+    }
+
+    /// Gibt die Namen aller Bildschirme kommagetrennt zurück, z.B. für Benachrichtigungen.
+    pub fn names(&self) -> String {
+        self.monitors.iter().map(|m| m.name.as_str()).collect::<Vec<_>>().join(", ")
+    }
+
+    /// Gibt die zuletzt abgefragten Rotationen aller Bildschirme zurück,
+    /// um sie mit [`restore`](Self::restore) wiederherstellen zu können.
+    pub fn current_rotations(&self) -> Vec<Option<Rotation>> {
+        self.monitors.iter().map(PlasmaMonitor::current_rotation).collect()
+    }
+
+    /// Rotiert alle Bildschirme zur angegebenen Rotation des Sensors.
+    ///
+    /// Schlägt die Rotation eines Bildschirms fehl, werden die bereits rotierten Bildschirme
+    /// in ihre vorherige Rotation zurückgedreht, damit die Bildschirme zueinander passend bleiben.
+    /// `previous` ist die bisherige Rotation des Sensors, sofern bekannt.
+    pub fn rotate(&self, backend: &DisplayBackend, rotation: Rotation, previous: Option<Rotation>) -> Result<()> {
+        for (i, monitor) in self.monitors.iter().enumerate() {
+            if let Err(e) = monitor.rotate(backend, rotation + monitor.offset) {
+                for done in &self.monitors[..i] {
+                    let Some(rollback) = previous.map(|p| p + done.offset).or(done.current_rotation()) else { continue };
+
+                    if let Err(e) = done.rotate(backend, rollback) {
+                        eprintln!("Bildschirm \"{}\" konnte nicht zurückgedreht werden: {e}", done.name);
+                    }
+                }
+
+                return Err(e.context(format!("Bildschirm \"{}\" konnte nicht rotiert werden", monitor.name)));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Stellt die mit [`current_rotations`](Self::current_rotations) gemerkten Rotationen wieder her.
+    /// Bildschirme mit unbekannter Rotation werden übersprungen.
+    pub fn restore(&self, backend: &DisplayBackend, rotations: &[Option<Rotation>]) -> Result<()> {
+        for (monitor, rotation) in self.monitors.iter().zip(rotations) {
+            match rotation {
+                Some(rotation) => monitor.rotate(backend, *rotation)?,
+                None => eprintln!("Ursprüngliche Rotation von \"{}\" unbekannt, sie kann nicht wiederhergestellt werden", monitor.name),
+            }
+        }
+
+        Ok(())
     }
 }
 
 
 /// Ordnet jeder der vier Bildschirmausrichtungen einen Richtungsvektor zu.
-#[derive(Serialize, Deserialize, Clone)]
-pub struct OrientationVectors(pub BTreeMap<Rotation, Vec3>);
+///
+/// Die Vektoren beschreiben die Erdbeschleunigung relativ zum Mittelpunkt [`offset`](Self::offset) der Rotation.
+/// Bei einer nach hinten geneigten Halterung oder einem Nullpunktfehler des Sensors liegen die Messungen
+/// auf einem Kreis, dessen Mittelpunkt nicht im Ursprung liegt; dieser Versatz wird vor jedem Vergleich abgezogen.
+/// Ältere Konfigurationen ohne Versatz und Normale bleiben gültig.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct OrientationVectors {
+    #[serde(flatten)]
+    pub vectors: BTreeMap<Rotation, Vec3>,
+
+    /// Mittelpunkt der Rotation, der von jedem Messwert abgezogen wird
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub offset: Vec3,
+
+    /// Normale der Rotationsebene aus der Ausgleichsrechnung.
+    /// Ohne Angabe steht sie senkrecht auf den Vektoren von `none` und `left`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub normal: Option<Vec3>,
+}
+
+/// Gibt an, ob ein Vektor null ist; wird für `skip_serializing_if` benötigt.
+fn is_zero(vec: &Vec3) -> bool {
+    *vec == Vec3::ZERO
+}
 
 impl OrientationVectors {
     /// Berechnet die vier Vektoren für die Bildschirmausrichtungen.
@@ -121,47 +860,566 @@ impl OrientationVectors {
         // Passe die Länge von `left_orthogonal` an, sodass sie der Länge von `down` entspricht.
         let left_final = left_orthogonal.normalize() * down.length();
 
-        Self(BTreeMap::from([
+        Self::centered(down, left_final, Vec3::ZERO, None)
+    }
+
+    /// Erzeugt die Vektoren aus den Richtungen von `none` und `left` relativ zum Mittelpunkt `offset`;
+    /// `inverted` und `right` zeigen jeweils in die Gegenrichtung.
+    pub fn centered(down: Vec3, left: Vec3, offset: Vec3, normal: Option<Vec3>) -> Self {
+        let vectors = BTreeMap::from([
             (Rotation::None, down),
             (Rotation::Inverted, -down),
-            (Rotation::Left, left_final),
-            (Rotation::Right, -left_final)
-        ]))
+            (Rotation::Left, left),
+            (Rotation::Right, -left),
+        ]);
+
+        Self { vectors, offset, normal }
+    }
+
+    /// Gibt den Messwert relativ zum Mittelpunkt der Rotation zurück.
+    pub fn relative(&self, acc: Vec3) -> Vec3 {
+        acc - self.offset
+    }
+
+    /// Gibt den Winkel (im Bogenmaß) zwischen dem Messwert und dem Richtungsvektor der angegebenen Ausrichtung zurück.
+    pub fn angle_to(&self, rotation: Rotation, acc: Vec3) -> f32 {
+        self.vectors[&rotation].angle_between(self.relative(acc))
+    }
+
+    /// Gibt den Winkel (im Bogenmaß) zurück, um den der Messwert aus der Rotationsebene heraus geneigt ist.
+    /// Die Rotationsebene verläuft durch den Mittelpunkt und steht senkrecht auf [`normal`](Self::normal).
+    /// Bei 0 steht der Bildschirm aufrecht, bei π/2 liegt er flach.
+    pub fn tilt_out_of_plane(&self, acc: Vec3) -> f32 {
+        let normal = self.normal
+            .unwrap_or_else(|| self.vectors[&Rotation::None].cross(self.vectors[&Rotation::Left]))
+            .normalize();
+        let relative = self.relative(acc);
+        (relative.dot(normal).abs() / relative.length()).clamp(0.0, 1.0).asin()
+    }
+
+    /// Wählt die Ausrichtung mit dem geringsten Winkel zwischen Mess- und Richtungsvektor
+    /// und gibt sie zusammen mit diesem Winkel zurück.
+    pub fn nearest(&self, acc: Vec3) -> (Rotation, f32) {
+        let relative = self.relative(acc);
+        self.vectors
+            .iter()
+            .map(|(&r, &a)| (r, a.angle_between(relative)))
+            .min_by(|(_, a1), (_, a2)| a1.total_cmp(a2))
+            .unwrap()
     }
 }
 
 
+/// Einstellungen für [`run_automatic_rotation`], die zur Laufzeit per `SIGHUP` neu geladen werden können.
+#[derive(Clone)]
+pub struct RotationSettings {
+    pub orientations: OrientationVectors,
+
+    /// Mindestwinkel in Grad, um den eine neue Ausrichtung besser passen muss (siehe [`RotationDebouncer`])
+    pub hysteresis_degrees: f32,
+
+    /// Mindestdauer, die eine neue Ausrichtung gewinnen muss (siehe [`RotationDebouncer`])
+    pub dwell_time: Duration,
+
+    /// Neigung aus der Rotationsebene in Grad, ab der die aktuelle Ausrichtung beibehalten wird
+    pub flat_threshold_degrees: f32,
+
+    /// Erkennung, ob das Gerät bewegt wird (siehe [`MotionDetector`])
+    pub motion: MotionSettings,
+
+    /// Sperre, solange die aktuelle Ausrichtung beibehalten wird
+    pub lock: RotationLock,
+
+    /// Befehle, die nach jeder Rotation ausgeführt werden
+    pub hooks: HookSettings,
+
+    /// Eingabegeräte, die zusammen mit dem Bildschirm rotiert werden
+    pub input_mapping: Option<InputMapping>,
+
+    /// Desktop-Benachrichtigungen; ohne Einstellungen werden keine gesendet
+    pub notifications: Option<NotificationSettings>,
+
+    /// Bedingungen, unter denen die Rotation vorübergehend angehalten wird
+    pub inhibitors: InhibitSettings,
+}
+
+impl RotationSettings {
+    /// Rotiert die konfigurierten Eingabegeräte zur angegebenen Rotation des Sensors.
+    /// Sie gehören zum in [`InputMapping::monitor`] angegebenen Bildschirm, ansonsten zum ersten der Gruppe.
+    /// Gehört der angegebene Bildschirm nicht zu dieser Gruppe, wird er von einem anderen Sensor rotiert
+    /// und es passiert nichts.
+    /// Fehler werden ausgegeben, beenden aber nicht das Programm.
+    fn apply_input_mapping(&self, group: &MonitorGroup, rotation: Rotation) {
+        let Some(input_mapping) = &self.input_mapping else { return };
+
+        let monitor = match &input_mapping.monitor {
+            Some(name) => group.monitors.iter().find(|m| &m.name == name),
+            None => group.monitors.first(),
+        };
+
+        if let Some(monitor) = monitor && let Err(e) = input_mapping.apply(&monitor.name, monitor.transform_for(rotation + monitor.offset)) {
+            eprintln!("Eingabegeräte konnten nicht rotiert werden: {e}");
+        }
+    }
+
+    /// Passt nach einer erfolgreichen Rotation die Eingabegeräte an, startet die Hooks aller Bildschirme
+    /// und sendet eine Benachrichtigung.
+    /// Gibt den Thread der Hooks zurück (siehe [`HookSettings::spawn`]).
+    fn after_rotation(&self, group: &MonitorGroup, old: Option<Rotation>, new: Rotation, acc: Vec3) -> Option<JoinHandle<()>> {
+        self.apply_input_mapping(group, new);
+
+        let events = group.monitors
+            .iter()
+            .map(|monitor| HookEvent {
+                old: old.map(|r| r + monitor.offset),
+                new: new + monitor.offset,
+                monitor_name: monitor.name.clone(),
+                acc,
+            })
+            .collect();
+        let hooks = self.hooks.spawn(events);
+
+        let rotations: Vec<String> = group.monitors.iter().map(|m| format!("{}: {}", m.name, new + m.offset)).collect();
+        self.notify(Category::Rotation, "Bildschirm rotiert", &rotations.join(", "));
+        hooks
+    }
+
+    /// Sendet eine Desktop-Benachrichtigung, sofern diese konfiguriert und ihre Kategorie eingeschaltet ist.
+    pub fn notify(&self, category: Category, summary: &str, body: &str) {
+        if let Some(notifications) = &self.notifications {
+            notifications.send(category, summary, body);
+        }
+    }
+
+    /// Passt die Einstellungen an einen Probelauf an: Die Sperre wird nur im Speicher geändert
+    /// (siehe [`RotationLock::in_memory`]) und es werden keine Benachrichtigungen gesendet.
+    pub fn for_dry_run(self, lock: &RotationLock) -> Self {
+        Self { lock: lock.clone(), notifications: None, ..self }
+    }
+
+    /// Erstellt einen [`RotationDebouncer`] mit den Werten dieser Einstellungen.
+    fn debouncer(&self) -> RotationDebouncer {
+        RotationDebouncer::new(self.hysteresis_degrees, self.dwell_time)
+    }
+}
+
 /// Liest Beschleunigungsdaten über die serielle Schnittstelle
-/// und rotiert den Bildschirm automatisch, sobald sich die Ausrichtung ändert.
-pub fn run_automatic_rotation(orientations: OrientationVectors, monitor: PlasmaMonitor, serial_reader: SerialReader) -> Result<()> {
-    let mut current_rotation = Rotation::None;
+/// und rotiert die Bildschirme automatisch, sobald sich die Ausrichtung ändert.
+///
+/// Der Zustand von `group` sollte vorher mit [`MonitorGroup::refresh_state`] abgefragt worden sein,
+/// damit die Schleife mit der tatsächlichen Rotation beginnt.
+/// Ist diese unbekannt, wird die erste gemessene Ausrichtung in jedem Fall angewendet.
+///
+/// Die Schleife endet, sobald `SIGINT` oder `SIGTERM` empfangen wurde.
+/// Bricht die Verbindung zum Sensor ab, wird ein [`SensorDisconnected`]-Fehler zurückgegeben.
+/// Bei `SIGHUP` werden die Einstellungen über `reload_settings` neu geladen und in `settings` ersetzt,
+/// damit sie auch nach einem erneuten Aufruf (z.B. nach einer Trennung des Sensors) gelten;
+/// bei `SIGUSR1` wird die Rotationssperre umgeschaltet.
+/// Die Bedingungen aus [`RotationSettings::inhibitors`] werden regelmäßig geprüft.
+pub fn run_automatic_rotation(
+    settings: &mut RotationSettings,
+    backend: &DisplayBackend,
+    group: &mut MonitorGroup,
+    serial_reader: &mut SerialReader,
+    signals: &SignalFlags,
+    reload_settings: impl Fn() -> Result<RotationSettings>,
+) -> Result<()> {
+    for monitor in &group.monitors {
+        if let Some(state) = &monitor.state && !(state.connected && state.enabled) {
+            eprintln!("Warnung: Bildschirm \"{}\" ist nicht verbunden oder deaktiviert", monitor.name);
+        }
+    }
+
+    let mut current_rotation = group.current_rotation();
+    let mut last_resync = Instant::now();
+    let mut debouncer = settings.debouncer();
+    let mut motion = MotionDetector::new(settings.motion.clone());
+    let mut inhibitor = Inhibitor::new(settings.inhibitors.clone());
+    let mut locked = settings.lock.is_locked();
+    let dry_run = backend.is_dry_run();
+    let mut failed_rotation = None;
+
+    // Die Eingabegeräte könnten noch nicht zur aktuellen Rotation passen, z.B. nach einem Neustart.
+    if let Some(rotation) = current_rotation && !dry_run {
+        settings.apply_input_mapping(group, rotation);
+    }
 
-    // Wiederhole, bis der serielle Datenstrom endet oder ein Fehler auftritt.
+    // Wiederhole, bis der serielle Datenstrom endet, ein Fehler auftritt oder das Programm beendet werden soll.
     for res in serial_reader {
         let acc = res?;
 
-        // Wähle die Ausrichtung mit der geringsten Differenz zwischen Mess- und Richtungsvektor.
-        let (r, _) = orientations.0
-            .iter()
-            .map(|(&r, &a)| (r, (a - acc).length()))
-            .min_by(|(_, a1), (_, a2)| a1.total_cmp(a2))
-            .unwrap();
+        if signals.should_terminate() {
+            break;
+        }
+
+        if signals.take_reload() {
+            match reload_settings() {
+                Ok(new_settings) => {
+                    *settings = new_settings;
+                    debouncer = settings.debouncer();
+                    motion = MotionDetector::new(settings.motion.clone());
+                    inhibitor = Inhibitor::new(settings.inhibitors.clone());
+                    eprintln!("Konfiguration neu geladen");
+                }
+                Err(e) => eprintln!("Konfiguration konnte nicht neu geladen werden: {e}"),
+            }
+        }
+
+        // Gleiche die Rotation regelmäßig mit dem Bildschirm ab,
+        // falls sie zwischenzeitlich von Hand geändert wurde.
+        // Ohne abfragbaren Zustand (Befehlsvorlagen, Probelauf) bleibt die zuletzt angewendete Rotation gültig;
+        // sie würde sonst unbekannt und bei jedem Abgleich erneut angewendet.
+        if last_resync.elapsed() >= RESYNC_INTERVAL && backend.reports_state() {
+            last_resync = Instant::now();
 
-        // `kscreen-doctor` muss nur aufgerufen werden, wenn sich die Rotation geändert hat.
-        if r != current_rotation {
-            monitor.rotate(r)?;
-            current_rotation = r;
+            match group.refresh_state(backend) {
+                Ok(()) => current_rotation = group.current_rotation(),
+                Err(e) => eprintln!("Rotation des Bildschirms konnte nicht abgefragt werden: {e}"),
+            }
+        }
+
+        // Der Ruhezustand wird auch während einer Sperre verfolgt, damit er beim Entsperren bereits bekannt ist.
+        motion.update(acc, Instant::now());
+
+        if signals.take_toggle_lock() && let Err(e) = settings.lock.toggle() {
+            eprintln!("{e}");
+        }
+
+        // Solange die Rotation gesperrt ist, wird die aktuelle Ausrichtung beibehalten.
+        if settings.lock.is_locked() != locked {
+            locked = !locked;
+            let message = format!("Rotation {}", if locked { "gesperrt" } else { "entsperrt" });
+            eprintln!("{message}");
+            settings.notify(Category::Lock, &message, &group.names());
+        }
+
+        if inhibitor.update(Instant::now()) {
+            match inhibitor.reason() {
+                Some(reason) => eprintln!("Rotation angehalten: {reason}"),
+                None => eprintln!("Rotation fortgesetzt"),
+            }
+        }
+
+        let hold_reason = if locked {
+            Some("gesperrt")
+        }
+        // Solange eine der konfigurierten Bedingungen zutrifft, z.B. ein Videoplayer läuft.
+        else if inhibitor.reason().is_some() {
+            Some("angehalten")
         }
+        // Während das Gerät bewegt wird, überwiegt die Beschleunigung durch die Bewegung.
+        // Eine Entscheidung ist erst möglich, wenn es wieder ruht.
+        else if !motion.is_resting() {
+            Some("in Bewegung")
+        }
+        // Liegt der Bildschirm flach, ist die Wahl der Ausrichtung zufällig; behalte daher die aktuelle bei.
+        else if filter::is_lying_flat(acc, &settings.orientations, settings.flat_threshold_degrees) {
+            Some("liegt flach")
+        } else {
+            None
+        };
+
+        if let Some(reason) = hold_reason {
+            debouncer.reset();
+            if dry_run {
+                log_decision(acc, &settings.orientations, reason);
+            }
+            continue;
+        }
+
+        // Wähle die Ausrichtung mit dem geringsten Winkel zwischen Mess- und Richtungsvektor.
+        // `kscreen-doctor` muss nur aufgerufen werden, wenn sich die Rotation geändert hat
+        // und die neue Ausrichtung lange und deutlich genug gewonnen hat.
+        let decision = debouncer.update(acc, &settings.orientations, current_rotation, Instant::now());
+
+        // Im Probelauf wird nur protokolliert; Eingabegeräte und Hooks bleiben unberührt.
+        if dry_run {
+            let outcome = match decision {
+                Some(r) => format!("rotiere zu {r}"),
+                None => format!("behalte {}", current_rotation.map_or("?".to_string(), |r| r.to_string())),
+            };
+            log_decision(acc, &settings.orientations, &outcome);
+            current_rotation = decision.or(current_rotation);
+            continue;
+        }
+
+        // Eine fehlgeschlagene Rotation wird beim nächsten Messwert erneut versucht,
+        // sofern die Ausrichtung dann noch gewinnt.
+        let decision = decision.or(failed_rotation.take().filter(|&r| settings.orientations.nearest(acc).0 == r));
+
+        if let Some(r) = decision {
+            if let Err(e) = group.rotate(backend, r, current_rotation) {
+                match backend.commands.on_failure {
+                    FailurePolicy::Fatal => return Err(e),
+                    FailurePolicy::Log => {
+                        eprintln!("Rotation fehlgeschlagen: {e:#}");
+                        settings.notify(Category::Failure, "Rotation fehlgeschlagen", &format!("{e:#}"));
+                        failed_rotation = Some(r);
+                        continue;
+                    }
+                }
+            }
+
+            // Die Hooks laufen im Hintergrund weiter; der Thread muss nicht abgewartet werden.
+            settings.after_rotation(group, current_rotation, r, acc);
+            current_rotation = Some(r);
+        }
+    }
+
+    // Ohne Abbruchsignal endet der Datenstrom nur, wenn der Sensor getrennt wurde.
+    if !signals.should_terminate() {
+        return Err(SensorDisconnected("Ende des Datenstroms erreicht".to_string()).into());
     }
 
     Ok(())
 }
 
-/// Hilfsfunktion, die einen Fehler zurückgibt, wenn `kscreen-doctor` einen anderen Exitstatus als 0 meldet.
-fn check_exit_status(exit_status: ExitStatus) -> Result<()> {
-    if exit_status.success() {
-        Ok(())
+/// Liest einige Messwerte, mittelt sie und rotiert die Bildschirme einmalig zur nächstgelegenen Ausrichtung.
+/// Gibt zurück, ob sich die Rotation geändert hat (im Probelauf: geändert hätte).
+///
+/// Hysterese und Verweildauer entfallen, da nur eine einzige Entscheidung getroffen wird.
+/// Ist die Rotation gesperrt oder angehalten oder liegt der Bildschirm flach, bleibt sie unverändert.
+pub fn rotate_once(
+    settings: &RotationSettings,
+    backend: &DisplayBackend,
+    group: &mut MonitorGroup,
+    serial_reader: &mut SerialReader,
+) -> Result<bool> {
+    let mut sum = Vec3::ZERO;
+    for _ in 0..ONE_SHOT_SAMPLES {
+        sum += serial_reader.next().ok_or_else(|| SensorDisconnected("Ende des Datenstroms erreicht".to_string()))??;
+    }
+    let acc = sum / ONE_SHOT_SAMPLES as f32;
+
+    // Auch im Probelauf wird der Zustand (nur lesend) abgefragt, damit das Ergebnis dem einer echten Rotation entspricht.
+    // Lässt er sich dort nicht abfragen, z.B. ohne Plasma, ist die Rotation unbekannt und gilt als geändert.
+    let dry_run = backend.is_dry_run();
+    match group.refresh_state(&backend.state_query()) {
+        Err(e) if dry_run => eprintln!("Aktuelle Rotation unbekannt: {e}"),
+        result => result?,
+    }
+    let current_rotation = group.current_rotation();
+
+    let mut inhibitor = Inhibitor::new(settings.inhibitors.clone());
+    inhibitor.update(Instant::now());
+    inhibitor.finish();
+
+    let hold_reason = if settings.lock.is_locked() {
+        Some("gesperrt")
+    } else if let Some(reason) = inhibitor.reason() {
+        Some(reason)
+    } else if filter::is_lying_flat(acc, &settings.orientations, settings.flat_threshold_degrees) {
+        Some("liegt flach")
     } else {
-        Err(anyhow!("kscreen-doctor wurde mit Status {exit_status} beendet"))
+        None
+    };
+
+    if let Some(reason) = hold_reason {
+        match dry_run {
+            true => log_decision(acc, &settings.orientations, reason),
+            false => eprintln!("Rotation nicht geändert: {reason}"),
+        }
+        return Ok(false);
+    }
+
+    let (rotation, _) = settings.orientations.nearest(acc);
+    let changed = current_rotation != Some(rotation);
+
+    if dry_run {
+        let outcome = match changed {
+            true => format!("rotiere zu {rotation}"),
+            false => format!("behalte {rotation}"),
+        };
+        log_decision(acc, &settings.orientations, &outcome);
+    } else if changed {
+        group.rotate(backend, rotation, current_rotation)?;
+
+        // Das Programm endet danach; die Hooks sollen trotzdem vollständig ausgeführt werden.
+        if let Some(hooks) = settings.after_rotation(group, current_rotation, rotation, acc) {
+            let _ = hooks.join();
+        }
+    }
+
+    Ok(changed)
+}
+
+/// Gibt im Probelauf eine Entscheidung zusammen mit Zeitpunkt (Unix-Zeit), Messwert,
+/// den Winkeln zu allen Richtungsvektoren und der nächstgelegenen Ausrichtung aus.
+fn log_decision(acc: Vec3, orientations: &OrientationVectors, decision: &str) {
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs_f64();
+    let angles: Vec<String> = orientations.vectors
+        .keys()
+        .map(|&r| format!("{r}={:.1}°", orientations.angle_to(r, acc).to_degrees()))
+        .collect();
+    let (nearest, _) = orientations.nearest(acc);
+
+    println!(
+        "{timestamp:.3} [{:.2}, {:.2}, {:.2}] {} nächste={nearest} -> {decision}",
+        acc.x, acc.y, acc.z,
+        angles.join(" "),
+    );
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, os::unix::fs::PermissionsExt, path::PathBuf};
+
+    use crate::command::CommandSettings;
+
+    use super::*;
+
+    /// Legt ein Skript an, das seine Argumente zeilenweise protokolliert
+    /// und mit Status 1 endet, wenn sie `fail_on` enthalten.
+    /// Gibt die Pfade von Skript und Protokoll zurück.
+    fn recording_stub(test_name: &str, fail_on: &str) -> (PathBuf, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("screen_rotator_{test_name}_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let log = dir.join("calls.log");
+        let stub = dir.join("stub.sh");
+        fs::write(&stub, format!("#!/bin/sh\necho \"$*\" >> {}\ncase \"$*\" in *{fail_on}*) exit 1;; esac\n", log.display())).unwrap();
+        fs::set_permissions(&stub, fs::Permissions::from_mode(0o755)).unwrap();
+
+        (stub, log)
+    }
+
+    fn custom_backend(list: Option<&str>, rotate: String) -> DisplayBackend {
+        DisplayBackend {
+            kind: BackendKind::Custom(CommandTemplates { list: list.map(str::to_string), rotate }),
+            commands: CommandSettings { retries: Some(0), ..Default::default() },
+        }
+    }
+
+    fn group(args: &[&str]) -> MonitorGroup {
+        MonitorGroup { monitors: args.iter().map(|arg| PlasmaMonitor::from_arg(arg).unwrap()).collect() }
+    }
+
+    #[test]
+    fn rotation_arithmetic() {
+        assert!(Rotation::Left + Rotation::Right == Rotation::None);
+        assert!(Rotation::Inverted + Rotation::Left == Rotation::Right);
+        assert!(Rotation::None - Rotation::Right == Rotation::Left);
+        assert!("270".parse::<Rotation>().unwrap() == Rotation::Left);
+        assert!("normal".parse::<Rotation>().unwrap() == Rotation::None);
+    }
+
+    #[test]
+    fn templates_receive_all_spellings_and_offsets() {
+        let (stub, log) = recording_stub("templates", "never");
+        let backend = custom_backend(None, format!("{} --output {{name}} {{rotation}} {{degrees}} {{xrandr}} {{reflect}}", stub.display()));
+
+        group(&["DP-1", "HDMI-1:right"]).rotate(&backend, Rotation::Left, None).unwrap();
+        group(&["DP-1"]).rotate(&backend, Rotation::None, None).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(log).unwrap(),
+            "--output DP-1 left 270 left normal\n--output HDMI-1 none 0 normal normal\n--output DP-1 none 0 normal normal\n",
+        );
+    }
+
+    #[test]
+    fn mirror_transforms() {
+        let rotations = [Rotation::None, Rotation::Left, Rotation::Right, Rotation::Inverted];
+
+        assert!(Mirror::Horizontal.apply(Rotation::None) == Transform { rotation: Rotation::None, flipped: true });
+        assert!(Mirror::Horizontal.apply(Rotation::Right) == Transform { rotation: Rotation::Left, flipped: true });
+        assert!(Mirror::Vertical.apply(Rotation::None) == Transform { rotation: Rotation::Inverted, flipped: true });
+        assert!(Mirror::Vertical.apply(Rotation::Left) == Transform { rotation: Rotation::Left, flipped: true });
+
+        for mirror in [Mirror::Horizontal, Mirror::Vertical] {
+            for rotation in rotations {
+                assert!(mirror.rotation_of(mirror.apply(rotation)) == Some(rotation));
+                assert!(mirror.rotation_of(Transform { rotation, flipped: false }).is_none());
+            }
+        }
+    }
+
+    #[test]
+    fn mirrored_monitors_pass_reflection_to_templates() {
+        let (stub, log) = recording_stub("reflect", "never");
+        let backend = custom_backend(None, format!("{} {{name}} {{xrandr}} {{reflect}}", stub.display()));
+        let monitor = PlasmaMonitor { mirror: Some(Mirror::Horizontal), ..PlasmaMonitor::from_name("DP-1".to_string()) };
+
+        MonitorGroup { monitors: vec![monitor] }.rotate(&backend, Rotation::Right, None).unwrap();
+
+        assert_eq!(fs::read_to_string(log).unwrap(), "DP-1 left x\n");
+    }
+
+    #[test]
+    fn failed_rotation_rolls_back_group() {
+        let (stub, log) = recording_stub("rollback", "HDMI-1");
+        let backend = custom_backend(None, format!("{} {{name}} {{rotation}}", stub.display()));
+
+        let result = group(&["DP-1", "HDMI-1"]).rotate(&backend, Rotation::Left, Some(Rotation::None));
+
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(log).unwrap(), "DP-1 left\nHDMI-1 left\nDP-1 none\n");
+    }
+
+    #[test]
+    fn lists_monitors_from_template_output() {
+        let backend = custom_backend(Some("printf DP-1\\n\\nHDMI-A-1\\n"), "true".to_string());
+
+        let names: Vec<_> = PlasmaMonitor::list(&backend).unwrap().into_iter().map(|m| m.name).collect();
+        assert_eq!(names, ["DP-1", "HDMI-A-1"]);
+    }
+
+    #[test]
+    fn edit_distance_counts_insertions_deletions_and_substitutions() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("DP-1", "DP-1"), 0);
+        assert_eq!(edit_distance("DP1", "DP-1"), 1);
+        assert_eq!(edit_distance("DP-1", "DP1"), 1);
+        assert_eq!(edit_distance("DP-1", "DP-2"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "eDP"), 3);
+        assert_eq!(edit_distance("ä", "a"), 1);
+    }
+
+    #[test]
+    fn missing_monitors_suggest_similar_names() {
+        let available = group(&["DP-2", "HDMI-A-1", "eDP-1"]).monitors;
+
+        assert_eq!(describe_missing("DP-2", &available), "Bildschirm \"DP-2\" ist nicht verbunden");
+        assert_eq!(describe_missing("dp2", &available), "Bildschirm \"dp2\" wurde nicht gefunden. Meinten Sie DP-2?");
+        assert_eq!(describe_missing("HDMI", &available), "Bildschirm \"HDMI\" wurde nicht gefunden. Meinten Sie HDMI-A-1?");
+        assert_eq!(
+            describe_missing("VGA-1", &available),
+            "Bildschirm \"VGA-1\" wurde nicht gefunden. Verfügbar: DP-2, HDMI-A-1, eDP-1",
+        );
+    }
+
+    #[test]
+    fn orientation_vectors_keep_old_format() {
+        let old = r#"{"None": [0, -9.8, 0], "Right": [9.8, 0, 0], "Inverted": [0, 9.8, 0], "Left": [-9.8, 0, 0]}"#;
+        let vectors: OrientationVectors = serde_json::from_str(old).unwrap();
+        assert!(vectors.offset == Vec3::ZERO && vectors.normal.is_none() && vectors.vectors.len() == 4);
+        assert!(!serde_json::to_string(&vectors).unwrap().contains("offset"));
+
+        let fitted = OrientationVectors { offset: Vec3::new(0.1, 0.2, -4.9), normal: Some(Vec3::Z), ..vectors };
+        let json = serde_json::to_string(&fitted).unwrap();
+        assert!(serde_json::from_str::<OrientationVectors>(&json).unwrap() == fitted);
+    }
+
+    #[test]
+    fn monitors_from_arguments_keep_saved_settings() {
+        let saved: Vec<PlasmaMonitor> = serde_json::from_str(r#"[
+            {"name": "DP-1", "mirror": "horizontal", "edid": {"vendor": "DEL", "model": "U2720Q", "serial": "1"},
+             "profiles": {"Left": {"scale": 1.5}}},
+            {"name": "HDMI-A-1"}
+        ]"#).unwrap();
+
+        let mut monitor = PlasmaMonitor::from_arg("DP-1:right").unwrap();
+        monitor.keep_saved_settings(&saved);
+        assert!(monitor.offset == Rotation::Right);
+        assert!(monitor.mirror == Some(Mirror::Horizontal));
+        assert!(monitor.edid.as_ref().is_some_and(|edid| edid.model == "U2720Q"));
+        assert_eq!(monitor.profiles.get(&Rotation::Left).and_then(|p| p.scale), Some(1.5));
+
+        let mut unknown = PlasmaMonitor::from_arg("DP-2").unwrap();
+        unknown.keep_saved_settings(&saved);
+        assert!(unknown.mirror.is_none() && unknown.edid.is_none() && unknown.profiles.is_empty());
     }
 }
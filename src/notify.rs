@@ -0,0 +1,281 @@
+//! Desktop-Benachrichtigungen über den D-Bus-Dienst `org.freedesktop.Notifications`.
+//!
+//! Läuft `rotate-monitor` im Hintergrund, bemerkt man sonst erst am ausbleibenden Drehen des Bildschirms,
+//! dass der Sensor getrennt wurde oder das Programm beendet ist.
+//! Die Benachrichtigungen werden wie die übrigen D-Bus-Aufrufe per `busctl --user` gesendet;
+//! dadurch lassen sie sich über `DBUS_SESSION_BUS_ADDRESS` auch an einen eigenen Bus
+//! mit einem Test-Benachrichtigungsdienst richten.
+
+use std::process::Command;
+
+use serde::{Deserialize, Serialize};
+
+use crate::command::CommandSettings;
+
+
+/// Maximale Laufzeit eines `busctl`-Aufrufs in Millisekunden.
+/// Ein hängender Benachrichtigungsdienst soll die Rotation nicht aufhalten.
+const BUSCTL_TIMEOUT_MS: u64 = 2000;
+
+
+/// Anlass einer Benachrichtigung; jede Kategorie kann einzeln abgeschaltet werden.
+#[derive(Clone, Copy)]
+pub enum Category {
+    /// Die Ausrichtung des Bildschirms hat sich geändert
+    Rotation,
+
+    /// Die Rotationssperre wurde umgeschaltet
+    Lock,
+
+    /// Der Sensor wurde getrennt oder wieder verbunden
+    Sensor,
+
+    /// Eine Rotation ist fehlgeschlagen oder die automatische Rotation wurde mit einem Fehler beendet
+    Failure,
+}
+
+impl Category {
+    /// Gibt den Namen des Symbols nach der freedesktop-Spezifikation zurück.
+    fn icon(self) -> &'static str {
+        match self {
+            Self::Rotation => "object-rotate-right",
+            Self::Lock => "object-locked",
+            Self::Sensor => "network-wired-disconnected",
+            Self::Failure => "dialog-error",
+        }
+    }
+
+    /// Gibt die Dringlichkeit zurück (0 = niedrig, 1 = normal, 2 = kritisch).
+    /// Kritische Benachrichtigungen werden nicht automatisch ausgeblendet.
+    fn urgency(self) -> u8 {
+        match self {
+            Self::Rotation | Self::Lock => 0,
+            Self::Sensor => 1,
+            Self::Failure => 2,
+        }
+    }
+}
+
+/// Legt fest, zu welchen Anlässen Benachrichtigungen gesendet werden.
+///
+/// Fehlende Felder in der Konfigurationsdatei erhalten ihren Standardwert (eingeschaltet).
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct NotificationSettings {
+    /// Benachrichtigung bei jeder Rotation
+    pub rotation: bool,
+
+    /// Benachrichtigung beim Sperren und Entsperren der Rotation
+    pub lock: bool,
+
+    /// Benachrichtigung, wenn der Sensor getrennt oder wieder verbunden wird
+    pub sensor: bool,
+
+    /// Benachrichtigung bei fehlgeschlagenen Rotationen und beim Beenden mit einem Fehler
+    pub failure: bool,
+
+    /// Anzeigedauer in Millisekunden; -1 überlässt sie dem Benachrichtigungsdienst
+    pub timeout_ms: i32,
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        Self {
+            rotation: true,
+            lock: true,
+            sensor: true,
+            failure: true,
+            timeout_ms: -1,
+        }
+    }
+}
+
+impl NotificationSettings {
+    /// Gibt an, ob Benachrichtigungen der angegebenen Kategorie gesendet werden.
+    fn is_enabled(&self, category: Category) -> bool {
+        match category {
+            Category::Rotation => self.rotation,
+            Category::Lock => self.lock,
+            Category::Sensor => self.sensor,
+            Category::Failure => self.failure,
+        }
+    }
+
+    /// Sendet eine Benachrichtigung, sofern ihre Kategorie eingeschaltet ist.
+    /// Fehler werden ausgegeben, beenden aber nicht das Programm.
+    pub fn send(&self, category: Category, summary: &str, body: &str) {
+        let Some(mut command) = self.notify_command(category, summary, body) else { return };
+
+        let commands = CommandSettings {
+            timeout_ms: Some(BUSCTL_TIMEOUT_MS),
+            retries: Some(0),
+            ..Default::default()
+        };
+
+        // Die Standardausgabe enthält nur die ID der Benachrichtigung und wird verworfen.
+        if let Err(e) = commands.run(&mut command, "busctl", true) {
+            eprintln!("Benachrichtigung konnte nicht gesendet werden: {e}");
+        }
+    }
+
+    /// Erzeugt den Aufruf von `Notify` mit der Signatur `susssasa{sv}i`:
+    /// Programmname, ersetzte ID, Symbol, Titel, Text, Aktionen, Hinweise und Anzeigedauer.
+    /// Gibt [`None`] zurück, wenn die Kategorie abgeschaltet ist.
+    fn notify_command(&self, category: Category, summary: &str, body: &str) -> Option<Command> {
+        if !self.is_enabled(category) {
+            return None;
+        }
+
+        let mut command = Command::new("busctl");
+        command
+            // Nach `--` werden auch Werte wie die Anzeigedauer -1 nicht als Option verstanden.
+            .args(["--user", "--", "call", "org.freedesktop.Notifications", "/org/freedesktop/Notifications"])
+            .args(["org.freedesktop.Notifications", "Notify", "susssasa{sv}i"])
+            .args(["screen_rotator", "0", category.icon(), summary, body])
+            // keine Aktionen, ein Hinweis (Dringlichkeit)
+            .args(["0", "1", "urgency", "y", &category.urgency().to_string()])
+            .arg(self.timeout_ms.to_string());
+
+        Some(command)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io::{BufRead, BufReader}, process::{Child, Stdio}, sync::mpsc, thread, time::{Duration, Instant}};
+
+    use super::*;
+    use crate::command::wait_with_timeout;
+
+    /// Programme, die für den Test benötigt werden.
+    const TOOLS: [&str; 4] = ["dbus-daemon", "dbus-monitor", "dbus-test-tool", "busctl"];
+
+    /// Maximale Wartezeit auf jeden Schritt des Tests.
+    const STEP_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Gibt an, ob das Programm in einem Verzeichnis aus `PATH` liegt.
+    fn installed(program: &str) -> bool {
+        std::env::var_os("PATH").is_some_and(|path| std::env::split_paths(&path).any(|dir| dir.join(program).is_file()))
+    }
+
+    /// Startet einen Prozess; ein Fehler lässt den Test mit dem Namen des Programms fehlschlagen.
+    fn spawn(command: &mut Command) -> Child {
+        command.spawn().unwrap_or_else(|e| panic!("{:?} konnte nicht gestartet werden: {e}", command.get_program()))
+    }
+
+    /// Eigener Session-Bus mit einem Dienst, der jeden Aufruf von `org.freedesktop.Notifications`
+    /// leer beantwortet, und einem Mitschnitt aller Aufrufe.
+    /// Alle Prozesse werden beim Verwerfen beendet.
+    struct MockNotificationServer {
+        address: String,
+        log: std::path::PathBuf,
+        processes: Vec<Child>,
+    }
+
+    impl MockNotificationServer {
+        /// Startet den Bus oder gibt [`None`] zurück, wenn eines der [`TOOLS`] nicht installiert ist.
+        /// Jeder Schritt wartet höchstens [`STEP_TIMEOUT`], bevor der Test fehlschlägt.
+        fn start() -> Option<Self> {
+            if !TOOLS.iter().all(|tool| installed(tool)) {
+                return None;
+            }
+
+            let mut daemon = spawn(Command::new("dbus-daemon")
+                .args(["--session", "--nofork", "--print-address"])
+                .stdout(Stdio::piped()));
+
+            // Die Adresse wird in einem eigenen Thread gelesen, damit ein hängender Bus den Test nicht blockiert.
+            let stdout = daemon.stdout.take().unwrap();
+            let (sender, receiver) = mpsc::channel();
+            thread::spawn(move || {
+                let mut address = String::new();
+                let _ = BufReader::new(stdout).read_line(&mut address);
+                let _ = sender.send(address);
+            });
+
+            let mut server = Self {
+                address: String::new(),
+                log: std::env::temp_dir().join(format!("screen_rotator_notify_{}.log", std::process::id())),
+                processes: vec![daemon],
+            };
+            server.address = receiver.recv_timeout(STEP_TIMEOUT).unwrap_or_default().trim().to_string();
+            assert!(!server.address.is_empty(), "dbus-daemon hat keine Adresse ausgegeben");
+
+            let monitor = spawn(Command::new("dbus-monitor")
+                .args(["--address", &server.address, "interface=org.freedesktop.Notifications"])
+                .stdout(fs::File::create(&server.log).unwrap()));
+            server.processes.push(monitor);
+
+            // Sobald der Mitschnitt seinen eigenen Namen abgegeben hat, empfängt er alle Nachrichten.
+            assert!(server.wait_for("member=NameLost").contains("member=NameLost"), "dbus-monitor nicht bereit");
+
+            let service = spawn(Command::new("dbus-test-tool")
+                .args(["echo", "--name=org.freedesktop.Notifications", "--session"])
+                .env("DBUS_SESSION_BUS_ADDRESS", &server.address));
+            server.processes.push(service);
+
+            Some(server)
+        }
+
+        /// Sendet die Benachrichtigung an diesen Bus.
+        /// Wiederholt den Aufruf, bis der Dienst seinen Namen registriert hat.
+        /// Auch ein hängender Aufruf wird nach [`STEP_TIMEOUT`] beendet.
+        fn send(&self, settings: &NotificationSettings, category: Category, summary: &str) {
+            let Some(mut command) = settings.notify_command(category, summary, "Text") else { return };
+            command.env("DBUS_SESSION_BUS_ADDRESS", &self.address).stdout(Stdio::null()).stderr(Stdio::null());
+
+            let deadline = Instant::now() + STEP_TIMEOUT;
+            loop {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if wait_with_timeout(&mut spawn(&mut command), remaining).is_ok_and(|status| status.success()) {
+                    return;
+                }
+
+                assert!(Instant::now() < deadline, "Benachrichtigungsdienst nicht erreichbar");
+                thread::sleep(Duration::from_millis(50));
+            }
+        }
+
+        /// Wartet höchstens [`STEP_TIMEOUT`], bis der Mitschnitt `text` enthält, und gibt ihn dann zurück.
+        fn wait_for(&self, text: &str) -> String {
+            let deadline = Instant::now() + STEP_TIMEOUT;
+            loop {
+                let log = fs::read_to_string(&self.log).unwrap_or_default();
+                if log.contains(text) || Instant::now() > deadline {
+                    return log;
+                }
+                thread::sleep(Duration::from_millis(50));
+            }
+        }
+    }
+
+    impl Drop for MockNotificationServer {
+        fn drop(&mut self) {
+            for process in self.processes.iter_mut().rev() {
+                let _ = process.kill();
+                let _ = process.wait();
+            }
+            let _ = fs::remove_file(&self.log);
+        }
+    }
+
+    #[test]
+    fn sends_enabled_categories_to_notification_server() {
+        let Some(server) = MockNotificationServer::start() else {
+            eprintln!("{} nicht vollständig installiert, Test übersprungen", TOOLS.join(", "));
+            return;
+        };
+        let settings = NotificationSettings { rotation: false, ..Default::default() };
+
+        server.send(&settings, Category::Rotation, "Abgeschaltet");
+        server.send(&settings, Category::Failure, "Rotation fehlgeschlagen");
+
+        let log = server.wait_for("Rotation fehlgeschlagen");
+        assert!(log.contains("member=Notify"));
+        assert!(log.contains("string \"dialog-error\""));
+        assert!(log.contains("string \"Rotation fehlgeschlagen\""));
+        assert!(log.contains("int32 -1"));
+        assert!(!log.contains("Abgeschaltet"));
+    }
+}
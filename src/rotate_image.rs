@@ -1,28 +1,43 @@
 //! Alle Funktionen, die benötigt werden, um ein Fenster zu öffnen und
 //! ein Bild darin parallel zum Erdboden ausgerichtet zu halten.
 
-use std::{cell::Cell, f32::consts::PI, path::Path, rc::Rc};
+use std::{cell::Cell, f32::consts::PI, path::Path, rc::Rc, time::Instant};
 
 use anyhow::{Result, anyhow};
 use image::{ImageBuffer, Rgba};
 use macroquad::prelude::*;
 use miniquad::window;
 
-use crate::{monitor::{OrientationVectors, Rotation}, serial::SerialReader};
+use crate::{filter::{self, MotionDetector, MotionSettings}, monitor::{OrientationVectors, Rotation}, serial::SerialReader, signals::SignalFlags};
 
 
+/// Einstellungen für [`run_image_stabilizer`].
+pub struct ImageSettings {
+    /// Öffnet das Fenster im Vollbildmodus
+    pub fullscreen: bool,
+
+    /// Farbe des Fensterhintergrunds hinter dem Bild
+    pub background_color: Color,
+
+    /// Neigung aus der Rotationsebene in Grad, ab der der Bildwinkel eingefroren wird
+    pub flat_threshold_degrees: f32,
+
+    /// Wenn gesetzt, wird der Bildwinkel eingefroren, solange das Gerät bewegt wird
+    pub motion: Option<MotionSettings>,
+}
+
 /// Öffnet das Fenster und lädt das Bild von der Datei in den Arbeitsspeicher.
 /// Der Rendervorgang ist vollständig in [`run_window_loop`] implementiert.
 pub fn run_image_stabilizer(
-    fullscreen: bool,
-    background_color: Color,
+    settings: ImageSettings,
     image_path: &Path,
     orientations: OrientationVectors,
-    serial_reader: SerialReader
+    serial_reader: SerialReader,
+    signals: SignalFlags
 ) -> Result<()> {
     // Konfiguration des Fensters.
     let config = Conf {
-        fullscreen,
+        fullscreen: settings.fullscreen,
         icon: None,
 
         ..Default::default()
@@ -40,10 +55,11 @@ pub fn run_image_stabilizer(
     macroquad::Window::from_config(
         config,
         run_window_loop(
-            background_color,
+            settings,
             rgb8a_img,
             orientations,
             serial_reader,
+            signals,
             error.clone()
         )
     );
@@ -58,10 +74,11 @@ pub fn run_image_stabilizer(
 /// Führt den Renderloop aus.
 /// Die Funktion muss als `async` markiert sein und darf keinen Rückgabewert haben.
 async fn run_window_loop(
-    background_color: Color,
+    settings: ImageSettings,
     image: ImageBuffer<Rgba<u8>, Vec<u8>>,
     orientations: OrientationVectors,
     mut serial_reader: SerialReader,
+    signals: SignalFlags,
     error: Rc<Cell<Option<anyhow::Error>>>
 ) {
     // Lade das Bild als GPU Textur in den VRAM.
@@ -80,10 +97,13 @@ async fn run_window_loop(
     let mut image_origin = Vec2::ZERO;
     let mut scaled_image_size = Vec2::ZERO;
 
+    let mut motion = settings.motion.map(MotionDetector::new);
+
     let mut rotation_paused = false;
     let mut angle: f32 = 0.0;
 
-    loop {
+    // Wiederhole, bis das Programm per Strg+C oder SIGTERM beendet wird.
+    while !signals.should_terminate() {
         // Lese alle Tastaturereignisse und pausiere die Rotation, wenn die Leertaste gedrückt wurde.
         loop {
             match macroquad::input::get_char_pressed() {
@@ -94,8 +114,21 @@ async fn run_window_loop(
         }
 
         // Warte auf den nächsten Beschleunigungswert und berechne den Winkel.
+        // Liegt der Bildschirm flach, ist der Winkel nicht bestimmbar und wird eingefroren.
+        // Wenn eingestellt, wird der Winkel auch eingefroren, solange das Gerät bewegt wird.
         let new_angle = match serial_reader.next() {
-            Some(Ok(acceleration)) => angle_from_vec(acceleration, &orientations),
+            Some(Ok(acceleration)) => {
+                let moving = motion.as_mut().is_some_and(|motion| {
+                    motion.update(acceleration, Instant::now());
+                    !motion.is_resting()
+                });
+
+                if moving || filter::is_lying_flat(acceleration, &orientations, settings.flat_threshold_degrees) {
+                    angle
+                } else {
+                    angle_from_vec(acceleration, &orientations)
+                }
+            }
             Some(Err(e)) => {
                 error.set(Some(e));
                 return;
@@ -161,7 +194,7 @@ async fn run_window_loop(
 
 
         // fülle den Hintergrund hinter dem Bild mit einer einheitlichen Farbe.
-        clear_background(background_color);
+        clear_background(settings.background_color);
 
         // Zeichne das Bild.
         draw_texture_ex(
@@ -185,8 +218,11 @@ async fn run_window_loop(
 /// Nutzt die Richtungsvektoren und den Beschleunigungswert, um die Rotation des Monitors zu bestimmen.
 fn angle_from_vec(vec: Vec3, orientations: &OrientationVectors) -> f32 {
     // Richtungsvektoren der Ausrichtungen `none` und `right`.
-    let orientations_none = orientations.0[&Rotation::None];
-    let orientations_right = orientations.0[&Rotation::Right];
+    let orientations_none = orientations.vectors[&Rotation::None];
+    let orientations_right = orientations.vectors[&Rotation::Right];
+
+    // Die Rotationsebene verläuft durch den Mittelpunkt der Rotation, nicht durch den Ursprung.
+    let vec = orientations.relative(vec);
 
     // Projiziere den Beschleunigungsvektor auf die Rotationsebene.
     let parallel_none = vec.project_onto(orientations_none);
@@ -217,3 +253,28 @@ fn get_corner_angles(size: Vec2) -> [f32; 4] {
     // Winkel aller Ecken, gemessen relativ zur Senkrechten durch den Mittelpunkt und die obere Kante.
     [inner_angle_a, 0.5*PI + inner_angle_b, PI + inner_angle_a, 1.5*PI + inner_angle_b]
 }
+
+
+#[cfg(test)]
+mod tests {
+    use glam::vec3;
+
+    use super::*;
+
+    #[test]
+    fn angle_is_measured_around_the_offset() {
+        // Halterung um 30° nach hinten geneigt: Alle Messungen enthalten denselben Anteil entlang der Drehachse.
+        let offset = vec3(0.0, 0.0, -4.9);
+        let orientations = OrientationVectors::centered(vec3(0.0, -8.5, 0.0), vec3(-8.5, 0.0, 0.0), offset, Some(Vec3::Z));
+
+        for (measurement, expected) in [
+            (vec3(0.0, -8.5, 0.0), 0.0),
+            (vec3(8.5, 0.0, 0.0), 0.5 * PI),
+            (vec3(0.0, 8.5, 0.0), PI),
+            (vec3(-8.5, 0.0, 0.0), 1.5 * PI),
+        ] {
+            let angle = angle_from_vec(measurement + offset, &orientations);
+            assert!((angle - expected).abs() < 1e-3, "{angle} statt {expected}");
+        }
+    }
+}
@@ -1,12 +1,14 @@
 //! Enthält Funktionen zum Bedienen der seriellen Schnittstelle.
 
-use std::{io::{BufRead, BufReader}, time::Duration};
+use std::{fmt::{self, Display}, io::{BufRead, BufReader}, time::Duration};
 
 use anyhow::{Result, anyhow};
 use glam::Vec3;
 use serde::{Deserialize, Serialize};
 use serialport::{SerialPort, SerialPortInfo};
 
+use crate::calibration::SensorCalibration;
+
 
 const BAUD_RATE: u32 = 9600;
 
@@ -51,6 +53,20 @@ impl SerialPortName {
 }
 
 
+/// Fehler, wenn die Verbindung zum Sensor abbricht, z.B. weil er ausgesteckt wurde oder keine Daten mehr sendet.
+/// Im Gegensatz zu anderen Fehlern kann der Anschluss danach erneut geöffnet werden.
+#[derive(Debug)]
+pub struct SensorDisconnected(pub String);
+
+impl Display for SensorDisconnected {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Verbindung zum Sensor unterbrochen: {}", self.0)
+    }
+}
+
+impl std::error::Error for SensorDisconnected {}
+
+
 /// Stellt einen geöffneten seriellen Anschluss dar.
 /// Dekodiert die eingelesenen Daten zu einem [`Vec3`].
 pub struct SerialReader {
@@ -58,7 +74,10 @@ pub struct SerialReader {
     reader: BufReader<Box<dyn SerialPort>>,
 
     /// Zwischenspeicher für die aktuell eingelesene Zeile, um ständige Neuallokationen zu vermeiden.
-    line: String
+    line: String,
+
+    /// Korrektur, die auf jeden Messwert angewendet wird
+    calibration: Option<SensorCalibration>,
 }
 
 impl SerialReader {
@@ -66,16 +85,30 @@ impl SerialReader {
     pub fn new(port: Box<dyn SerialPort>) -> Self {
         Self {
             line: String::new(),
-            reader: BufReader::new(port)
+            reader: BufReader::new(port),
+            calibration: None,
         }
     }
 
+    /// Legt die Korrektur fest, die auf jeden folgenden Messwert angewendet wird.
+    /// Ohne Korrektur werden die Rohwerte des Sensors zurückgegeben.
+    pub fn set_calibration(&mut self, calibration: Option<SensorCalibration>) {
+        self.calibration = calibration;
+    }
+
+    /// Gibt die aktuell angewendete Korrektur zurück.
+    pub fn calibration(&self) -> Option<&SensorCalibration> {
+        self.calibration.as_ref()
+    }
+
     /// Liest eine Zeile vom seriellen Stream.
     /// Bei Erreichen des Endes wird `Ok(None)` zurückgegeben.
-    /// Wenn ein Fehler auftritt, wird dieser zurückgegeben.
+    /// Wenn ein Fehler auftritt, wird er als [`SensorDisconnected`] zurückgegeben.
     fn read_line(&mut self) -> Result<Option<&str>>{
         self.line.clear();
-        let bytes_read = self.reader.read_line(&mut self.line)?;
+        let bytes_read = self.reader
+            .read_line(&mut self.line)
+            .map_err(|e| SensorDisconnected(e.to_string()))?;
 
         if bytes_read == 0 {
             Ok(None)
@@ -107,7 +140,7 @@ impl Iterator for SerialReader {
                 Err(err) => return Some(Err(err)),
                 Ok(None) => return None,
                 Ok(Some(str)) => match Self::parse_line(str) {
-                    Some(acc) => return Some(Ok(acc)),
+                    Some(acc) => return Some(Ok(self.calibration.as_ref().map_or(acc, |c| c.apply(acc)))),
                     None => continue
                 }
             }
@@ -0,0 +1,96 @@
+//! Behandlung von Signalen, mit denen das Programm von außen gesteuert wird:
+//! - `SIGINT` (Strg+C) und `SIGTERM` (z.B. von systemd) beenden das Programm geordnet.
+//! - `SIGHUP` lädt die Konfigurationsdatei neu.
+//! - `SIGUSR1` schaltet die Rotationssperre um.
+//!
+//! `SIGHUP` und `SIGUSR1` werden nur abgefangen, solange die automatische Rotation läuft
+//! (siehe [`SignalFlags::handle_reload_and_lock`]); in allen anderen Modi beenden sie das Programm wie gewohnt.
+
+use std::sync::{atomic::{AtomicBool, Ordering}, Arc};
+
+use anyhow::Result;
+use signal_hook::{consts::{SIGINT, SIGTERM}, flag};
+
+
+/// Merkt sich, welche Signale seit der letzten Abfrage empfangen wurden.
+///
+/// Die Signal-Handler setzen lediglich atomare Flags, die im Hauptprogramm regelmäßig abgefragt werden.
+/// So kann das Programm z.B. vor dem Beenden noch die ursprüngliche Rotation wiederherstellen.
+#[derive(Clone)]
+pub struct SignalFlags {
+    terminate: Arc<AtomicBool>,
+    reload: Arc<AtomicBool>,
+    toggle_lock: Arc<AtomicBool>,
+}
+
+impl SignalFlags {
+    /// Registriert die Signal-Handler für `SIGINT` und `SIGTERM`.
+    ///
+    /// Wird `SIGINT` oder `SIGTERM` ein zweites Mal empfangen, bevor das Programm sich beendet hat,
+    /// wird es sofort abgebrochen, damit ein hängendes Programm trotzdem beendet werden kann.
+    pub fn register() -> Result<Self> {
+        let terminate = Arc::new(AtomicBool::new(false));
+        let reload = Arc::new(AtomicBool::new(false));
+        let toggle_lock = Arc::new(AtomicBool::new(false));
+
+        for signal in [SIGINT, SIGTERM] {
+            // Die Reihenfolge ist wichtig: Beim ersten Signal ist das Flag noch nicht gesetzt,
+            // sodass nur der zweite Handler greift.
+            flag::register_conditional_shutdown(signal, 1, terminate.clone())?;
+            flag::register(signal, terminate.clone())?;
+        }
+
+        Ok(Self { terminate, reload, toggle_lock })
+    }
+
+    /// Registriert zusätzlich die Signal-Handler für `SIGHUP` und `SIGUSR1`.
+    /// Nur aufrufen, wenn die Flags auch abgefragt werden; sonst würden die Signale verschluckt.
+    pub fn handle_reload_and_lock(&self) -> Result<()> {
+        #[cfg(unix)]
+        {
+            flag::register(signal_hook::consts::SIGHUP, self.reload.clone())?;
+            flag::register(signal_hook::consts::SIGUSR1, self.toggle_lock.clone())?;
+        }
+
+        Ok(())
+    }
+
+    /// Erzeugt eine Kopie mit eigenem Flag für `SIGHUP`.
+    ///
+    /// Da [`take_reload`](Self::take_reload) das Flag zurücksetzt, würde bei mehreren Threads
+    /// sonst nur einer von ihnen die Konfiguration neu laden.
+    /// Die übrigen Flags bleiben geteilt; die Rotationssperre wird ohnehin über eine gemeinsame Datei umgeschaltet.
+    pub fn with_own_reload(&self) -> Result<Self> {
+        let reload = Arc::new(AtomicBool::new(false));
+
+        #[cfg(unix)]
+        flag::register(signal_hook::consts::SIGHUP, reload.clone())?;
+
+        Ok(Self { reload, ..self.clone() })
+    }
+
+    /// Erzeugt Flags ohne Signal-Handler, bei denen das Beenden bereits angefordert ist.
+    #[cfg(test)]
+    pub fn terminated() -> Self {
+        Self {
+            terminate: Arc::new(AtomicBool::new(true)),
+            reload: Arc::new(AtomicBool::new(false)),
+            toggle_lock: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Gibt an, ob das Programm beendet werden soll.
+    pub fn should_terminate(&self) -> bool {
+        self.terminate.load(Ordering::Relaxed)
+    }
+
+    /// Gibt an, ob die Konfiguration neu geladen werden soll, und setzt das Flag zurück.
+    pub fn take_reload(&self) -> bool {
+        self.reload.swap(false, Ordering::Relaxed)
+    }
+
+    /// Gibt an, ob die Rotationssperre umgeschaltet werden soll, und setzt das Flag zurück.
+    pub fn take_toggle_lock(&self) -> bool {
+        self.toggle_lock.swap(false, Ordering::Relaxed)
+    }
+}